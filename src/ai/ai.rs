@@ -6,16 +6,18 @@ use std::time::{ Duration, Instant };
 
 use super::Game;
 use super::tree::*;
+use super::game::stable_hash;
+use super::config::{ SearchConfig, seeded_rng };
 use ai::montecarlo::montecarlo;
 use ai::sim_thread_pool::SimThreadPool;
 
-use rand::{ thread_rng, Rng };
-
 #[derive(Debug)]
 pub enum Request<G: Game> {
     Info,
 
     MakeMove(G::Move),
+    Configure(SearchConfig), // takes effect from the next iteration of the search
+    Think(SearchBudget, Sender<Decision<G>>), // the decision is sent once the budget is used up
     //MakeRandomMove,
 }
 
@@ -28,11 +30,44 @@ pub enum Response<G: Game> {
         confidence: f64,
         total_sims: u64,
         time_elapsed: Duration,
+        moves: Vec<MoveStats<G>>, // every move from the root
     },
 
     Ok,
 }
 
+// How long to think for, counted from when the request is made
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchBudget {
+    Time(Duration),
+    Nodes(usize), // until the tree has this many nodes
+    Simulations(u64),
+}
+
+// How many moves deep the principal variation of each move goes
+const PV_DEPTH: usize = 8;
+
+// What the search thinks of one move from the root
+#[derive(Debug)]
+pub struct MoveStats<G: Game> {
+    pub mv: G::Move,
+    pub simulations: u32, // times the search went through this move
+    pub games: u32,
+    pub reward: f64, // the average, for the player making the move
+    pub rewards: Vec<(G::Player, f64)>, // the average for every player with a share in a playout through this move
+    pub uct: Option<f64>, // None if the move is down to chance or hasn't been played out yet
+    pub principal_variation: Vec<G::Move>, // the most visited line after this move
+}
+
+#[derive(Debug)]
+pub struct Decision<G: Game> {
+    pub best_move: Option<G::Move>, // None if the next move is down to chance, or there are no moves
+    pub is_random: bool,
+    pub moves: Vec<MoveStats<G>>,
+    pub simulations: u64, // while thinking about this decision
+    pub time_elapsed: Duration,
+}
+
 pub(in super) type NodeID = u64;
 
 pub(in super) struct NodeList<G: Game> {
@@ -45,6 +80,10 @@ impl<G: Game> NodeList<G> {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
     pub fn add(&mut self, node: MoveTreeNode<G>) -> NodeID {
         let id = node.game.get_hash();
         self.nodes.insert(id, RefCell::new(node));
@@ -102,7 +141,12 @@ impl<G: Game> NodeList<G> {
 
 fn best_move<G: Game>(nodes: &NodeList<G>, root: NodeID) -> Option<G::Move> {
     let rt = nodes.get(root);
-    let opt_mv = rt.children.iter().map(|e| {
+
+    // ties go to the same move every time
+    let mut children: Vec<(&G::Move, &NodeID)> = rt.children.iter().collect();
+    children.sort_by_key(|&(mv, _)| stable_hash(mv));
+
+    let opt_mv = children.into_iter().map(|e| {
         let (k, child_id) = e;
         let child = nodes.get(*child_id);
 
@@ -114,25 +158,97 @@ fn best_move<G: Game>(nodes: &NodeList<G>, root: NodeID) -> Option<G::Move> {
     opt_mv.map(|i| i.1.clone())
 }
 
+fn principal_variation<G: Game>(nodes: &NodeList<G>, from: NodeID) -> Vec<G::Move> {
+    let mut line = Vec::new();
+    let mut node_id = from;
+    while line.len() < PV_DEPTH {
+        // chance nodes follow the outcome looked at most, which is usually the likeliest
+        let mv = match best_move(nodes, node_id) {
+            Some(mv) => mv,
+            None => break,
+        };
+        node_id = *nodes.get(node_id).children.get(&mv).unwrap();
+        if nodes.get(node_id).simulations == 0 {
+            break;
+        }
+        line.push(mv);
+    }
+    line
+}
+
+fn move_stats<G: Game>(nodes: &NodeList<G>, root: NodeID, exploration: f64) -> Vec<MoveStats<G>> {
+    let rt = nodes.get(root);
+    let is_random = rt.weights.is_some();
+
+    let mut moves: Vec<MoveStats<G>> = rt.children.iter().map(|(mv, child_id)| {
+        let child = nodes.get(*child_id);
+
+        let mut rewards: Vec<(G::Player, f64)> = child.rewards.keys().map(|player| {
+            (player.clone(), child.value_for(player))
+        }).collect();
+        rewards.sort_by_key(|&(ref player, _)| stable_hash(player));
+
+        // worked out afresh, as the stored scores are only updated when the search passes by
+        let reward = child.value_for(&rt.player);
+        let uct = if is_random || child.games == 0 {
+            None
+        } else {
+            Some(reward + exploration * ((rt.games as f64).ln() / (child.games as f64)).sqrt())
+        };
+
+        MoveStats {
+            mv: mv.clone(),
+            simulations: child.simulations,
+            games: child.games,
+            reward,
+            rewards,
+            uct,
+            principal_variation: principal_variation(nodes, *child_id),
+        }
+    }).collect();
+
+    // most visited first
+    moves.sort_by_key(|stats| stable_hash(&stats.mv));
+    moves.sort_by(|a, b| b.simulations.cmp(&a.simulations));
+    moves
+}
+
+fn decision<G: Game>(nodes: &NodeList<G>, root: NodeID, config: &SearchConfig, simulations: u64, time_elapsed: Duration) -> Decision<G> {
+    let is_random = nodes.get(root).weights.is_some();
+    Decision {
+        best_move: if is_random { None } else { best_move(nodes, root) },
+        is_random,
+        moves: move_stats(nodes, root, config.exploration),
+        simulations,
+        time_elapsed,
+    }
+}
+
 pub struct Ai<G: Game> {
     to_thread: Sender<Request<G>>,
     from_thread: Receiver<Response<G>>,
 }
 
 impl<G> Ai<G> where G: Game + 'static {
-    pub fn new(game: G) -> Self {
+    pub fn new(game: G, config: SearchConfig) -> Self {
         let (to_thread, from_outside) = channel();
         let (to_outside, from_thread) = channel();
 
         thread::spawn(move || {
             let start_time = Instant::now();
 
-            let mut rand = thread_rng();
+            let mut config = config;
+            let mut seed = config.master_seed();
+            let mut rand = seeded_rng(seed);
+            let mut iterations: u64 = 0; // since the root last changed
 
             let mut num_sims: u64 = 0;
             let mut nodes = NodeList::new();
             let mut root = nodes.add(MoveTreeNode::new(game));
-            let thread_pool = SimThreadPool::new();
+            let mut thread_pool = SimThreadPool::new(config.workers, seed);
+
+            // (budget, when it started, simulations so far then, where to send the decision)
+            let mut thinking: Vec<(SearchBudget, Instant, u64, Sender<Decision<G>>)> = Vec::new();
 
             loop {
                 //println!("#nodes: {}", nodes.len());
@@ -155,7 +271,7 @@ impl<G> Ai<G> where G: Game + 'static {
                                 })
                                 .map(|c_id| {
                                     let c = nodes.get(*c_id);
-                                    c.wins as f64 / c.games as f64
+                                    c.value_for(&rt.player)
                                 })
                                 .unwrap_or(0.0);
 
@@ -166,13 +282,19 @@ impl<G> Ai<G> where G: Game + 'static {
                                 confidence: confidence,
                                 total_sims: num_sims,
                                 time_elapsed: start_time.elapsed(),
+                                moves: move_stats(&nodes, root, config.exploration),
                             };
 
                             to_outside.send(stats).expect("Send failed (Info)");
                         },
 
                         Request::MakeMove(mv) => {
-                            println!("{:?}", mv);
+                            // anyone still thinking about the old position gets what there is so far
+                            for (_, started, sims_before, reply) in thinking.drain(..) {
+                                let _ = reply.send(decision(&nodes, root, &config, num_sims - sims_before, started.elapsed()));
+                            }
+
+                            iterations = 0;
                             root = {
                                 let rt = nodes.take(root);
                                 let new_root_id = {
@@ -196,6 +318,19 @@ impl<G> Ai<G> where G: Game + 'static {
                             to_outside.send(Response::Ok).expect("Send failed (Ok)");
                         },
 
+                        Request::Configure(new_config) => {
+                            if new_config.workers != config.workers || new_config.seed != config.seed {
+                                seed = new_config.master_seed();
+                                rand = seeded_rng(seed);
+                                thread_pool = SimThreadPool::new(new_config.workers, seed);
+                            }
+                            config = new_config;
+                        },
+
+                        Request::Think(budget, reply) => {
+                            thinking.push((budget, Instant::now(), num_sims, reply));
+                        },
+
                         /*Request::MakeRandomMove => {
                             let rt = nodes.take(root);
                             let mvs: Vec<G::Move> = rt.children.keys().cloned().collect();
@@ -232,7 +367,34 @@ impl<G> Ai<G> where G: Game + 'static {
                     }
                 };
 
-                num_sims += montecarlo(&mut nodes, root, &thread_pool) as u64;
+                let budget_left = config.iterations_per_move.map_or(true, |max| iterations < max);
+                let can_search = nodes.len() < config.max_tree_size && budget_left;
+                if can_search {
+                    num_sims += montecarlo(&mut nodes, root, &thread_pool, &config, &mut rand) as u64;
+                    iterations += 1;
+                }
+
+                // thinking also stops if the search can't go any further
+                let game_over = nodes.get(root).game.get_winner().is_some();
+                let mut still_thinking = Vec::new();
+                for (budget, started, sims_before, reply) in thinking.drain(..) {
+                    let done = !can_search || game_over || match budget {
+                        SearchBudget::Time(time) => started.elapsed() >= time,
+                        SearchBudget::Nodes(max) => nodes.len() >= max,
+                        SearchBudget::Simulations(max) => num_sims - sims_before >= max,
+                    };
+                    if done {
+                        let _ = reply.send(decision(&nodes, root, &config, num_sims - sims_before, started.elapsed()));
+                    } else {
+                        still_thinking.push((budget, started, sims_before, reply));
+                    }
+                }
+                thinking = still_thinking;
+
+                if !can_search {
+                    // wait for a move to be made; the old branches are dropped then
+                    thread::sleep(Duration::from_millis(10));
+                }
             }
         });
 
@@ -258,7 +420,91 @@ impl<G> Ai<G> where G: Game + 'static {
         self.to_thread.send(Request::MakeMove(mv)).unwrap();
     }
 
+    pub fn configure(&self, config: SearchConfig) {
+        self.to_thread.send(Request::Configure(config)).unwrap();
+    }
+
+    // Starts thinking about the current position; the decision arrives on the returned
+    // channel once the budget is used up
+    pub fn start_thinking(&self, budget: SearchBudget) -> Receiver<Decision<G>> {
+        let (reply, decision) = channel();
+        self.to_thread.send(Request::Think(budget, reply)).unwrap();
+        decision
+    }
+
+    // Thinks about the current position until the budget is used up
+    pub fn think(&self, budget: SearchBudget) -> Decision<G> {
+        self.start_thinking(budget).recv().expect("The AI thread stopped while thinking")
+    }
+
     /*pub fn make_random_move(&self) {
         self.to_thread.send(Request::MakeRandomMove).unwrap();
     }*/
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use ai::{ Game, MoveList, LeafBudget };
+
+    // Two players take turns adding to their total, then roll to add a bit more; the
+    // first to 15 wins
+    #[derive(Clone, Hash, Debug)]
+    struct Race {
+        totals: [u8; 2],
+        cur_player: u8,
+        rolling: bool,
+    }
+
+    impl Game for Race {
+        type Move = u8;
+        type Player = u8;
+
+        fn available_moves(&self) -> MoveList<u8> {
+            if self.get_winner().is_some() {
+                MoveList::Choice(Vec::new())
+            } else if self.rolling {
+                MoveList::Random(vec![(0, 0.5), (1, 0.3), (2, 0.2)])
+            } else {
+                MoveList::Choice(vec![1, 2, 3])
+            }
+        }
+        fn make_move(&mut self, mv: &u8) {
+            self.totals[self.cur_player as usize] += *mv;
+            if self.rolling {
+                self.cur_player = 1 - self.cur_player;
+            }
+            self.rolling = !self.rolling;
+        }
+        fn get_cur_player(&self) -> u8 {
+            self.cur_player
+        }
+        fn get_winner(&self) -> Option<u8> {
+            (0..2).find(|player| self.totals[*player as usize] >= 15)
+        }
+    }
+
+    #[test]
+    fn seeded_searches_are_reproducible() {
+        let config = SearchConfig {
+            leaf_budget: LeafBudget::Playouts(4),
+            workers: 2,
+            iterations_per_move: Some(300),
+            seed: Some(12345),
+            ..SearchConfig::default()
+        };
+        let game = Race { totals: [0, 0], cur_player: 0, rolling: false };
+
+        let decisions: Vec<Decision<Race>> = (0..2).map(|_| {
+            let ai = Ai::new(game.clone(), config.clone());
+            // thinking stops once the search has used up its iterations
+            ai.think(SearchBudget::Time(Duration::from_secs(60)))
+        }).collect();
+
+        assert!(decisions[0].best_move.is_some());
+        assert_eq!(decisions[0].best_move, decisions[1].best_move);
+        assert_eq!(format!("{:?}", decisions[0].moves), format!("{:?}", decisions[1].moves));
+    }
+}
@@ -0,0 +1,52 @@
+use rand::{ thread_rng, Rng, SeedableRng, XorShiftRng };
+use num_cpus;
+
+// How much simulating each new leaf of the tree gets
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LeafBudget {
+    Millis(u64), // playouts for this long on every worker
+    Playouts(u32), // this many playouts on every worker
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SearchConfig {
+    pub exploration: f64, // the UCT exploration constant
+    pub leaf_budget: LeafBudget,
+    pub workers: usize, // threads running playouts
+    pub max_tree_size: usize, // the search pauses once the tree has this many nodes
+    pub iterations_per_move: Option<u64>, // the search pauses after this many iterations from each root
+    pub max_playout_moves: Option<u32>, // longer playouts are cut off and scored with `Game::get_rewards`
+    pub seed: Option<u64>, // None picks a different seed every time
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            exploration: 2.0f64.sqrt(),
+            leaf_budget: LeafBudget::Millis(25),
+            workers: num_cpus::get(),
+            max_tree_size: 2_000_000,
+            iterations_per_move: None,
+            max_playout_moves: Some(1000),
+            seed: None,
+        }
+    }
+}
+
+impl SearchConfig {
+    // With a seed, a `Playouts` leaf budget and a limit on iterations, searching the same
+    // position always gives the same result
+    pub(in super) fn master_seed(&self) -> u64 {
+        self.seed.unwrap_or_else(|| thread_rng().gen())
+    }
+}
+
+pub(crate) fn seeded_rng(seed: u64) -> XorShiftRng {
+    // xorshift can't be seeded with all zeros
+    XorShiftRng::from_seed([seed as u32, (seed >> 32) as u32, 0x2545F491, 0x9E3779B9])
+}
+
+// Each worker gets its own stream of random numbers, derived from the master seed
+pub(in super) fn worker_rng(seed: u64, worker: usize) -> XorShiftRng {
+    seeded_rng(seed ^ (worker as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15))
+}
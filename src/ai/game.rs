@@ -2,6 +2,14 @@ use std::hash::{ Hash, Hasher };
 use std::collections::hash_map::DefaultHasher;
 use std::fmt::Debug;
 
+// The same for equal values in every run, unlike HashMap iteration order, so moves can be
+// put in an order that a seeded search can reproduce
+pub(in super) fn stable_hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
 pub enum MoveList<T> {
     Random(Vec<(T, f64)>),
     Choice(Vec<T>),
@@ -16,6 +24,12 @@ pub trait Game: Clone + Hash + Send {
     fn get_cur_player(&self) -> Self::Player;
     fn get_winner(&self) -> Option<Self::Player>;
 
+    // What each player gets out of a playout that has stopped, as a share of a win. By default
+    // the winner gets everything, and nobody gets anything if the playout was cut off.
+    fn get_rewards(&self) -> Vec<(Self::Player, f64)> {
+        self.get_winner().map(|winner| vec![(winner, 1.0)]).unwrap_or_else(Vec::new)
+    }
+
     fn get_hash(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
         self.hash(&mut hasher);
@@ -1,8 +1,10 @@
 pub mod game;
 pub mod ai;
+mod config;
 mod tree;
 mod montecarlo;
 mod sim_thread_pool;
 
 pub use self::ai::*;
-pub use self::game::*;
\ No newline at end of file
+pub use self::game::*;
+pub use self::config::*;
\ No newline at end of file
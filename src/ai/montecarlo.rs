@@ -1,15 +1,15 @@
 use std::f64::INFINITY;
 use std::collections::HashMap;
 
-use rand::{ thread_rng, Rng };
+use rand::{ Rng, XorShiftRng };
 
 use super::{ Game, MoveList };
+use super::game::stable_hash;
 use super::tree::*;
 use super::ai::{ NodeID, NodeList };
+use super::config::SearchConfig;
 use ai::sim_thread_pool::SimThreadPool;
 
-const EXPLORATION_FACTOR: f64 = 1.4142135623730950488016887242097; // sqrt(2)
-
 fn all_max<'a, G, K, I>(list: I, nodes: &mut NodeList<G>) -> (f64, Vec<(&'a K, &'a NodeID)> )
     where I: Iterator<Item=(&'a K, &'a NodeID)>,
           G: Game
@@ -29,25 +29,28 @@ fn all_max<'a, G, K, I>(list: I, nodes: &mut NodeList<G>) -> (f64, Vec<(&'a K, &
     })
 }
 
-fn random_choice<'a, G, I>(list: I, weights: &HashMap<G::Move, f64>, nodes: &mut NodeList<G>) -> (&'a G::Move, &'a NodeID)
+fn random_choice<'a, G, I>(list: I, weights: &HashMap<G::Move, f64>, nodes: &mut NodeList<G>, rand: &mut XorShiftRng) -> (&'a G::Move, &'a NodeID)
     where I: Iterator<Item=(&'a G::Move, &'a NodeID)>,
           G: Game
 {
-    let n = thread_rng().next_f64();
+    let mut list: Vec<(&G::Move, &NodeID)> = list.collect();
+    list.sort_by_key(|&(mv, _)| stable_hash(mv));
+
+    let n = rand.next_f64();
     let mut sum = 0.0;
-    for (mv, node_id) in list.into_iter() {
+    for &(mv, node_id) in list.iter() {
         let weight = weights.get(mv).unwrap();
         sum += *weight;
         if sum >= n {
             return (mv, node_id);
         }
     }
-    unreachable!();
+    // the weights can add up to a little under 1
+    *list.last().unwrap()
 }
 
-pub(in super) fn montecarlo<G: Game + 'static>(nodes: &mut NodeList<G>, root: NodeID, thread_pool: &SimThreadPool<G>) -> u32 {
-    let mut rand = thread_rng();
-
+pub(in super) fn montecarlo<G: Game + 'static>(nodes: &mut NodeList<G>, root: NodeID, thread_pool: &SimThreadPool<G>,
+                                               config: &SearchConfig, rand: &mut XorShiftRng) -> u32 {
     // Select
     let mut cur_node_id = root;
     let mut sim_this = false;
@@ -58,14 +61,15 @@ pub(in super) fn montecarlo<G: Game + 'static>(nodes: &mut NodeList<G>, root: No
 
         if node.children.len() > 0 {
             if let Some(ref weights) = node.weights {
-                let (mv, child) = random_choice(node.children.iter(), weights, nodes);
+                let (mv, child) = random_choice(node.children.iter(), weights, nodes, rand);
                 path.push((cur_node_id, mv.clone()));
 
                 cur_node_id = *child;
 
                 // no need to simulate a random node because there is no choice to be made
             } else {
-                let (max_val, max_list) = all_max(node.children.iter(), nodes);
+                let (max_val, mut max_list) = all_max(node.children.iter(), nodes);
+                max_list.sort_by_key(|&(mv, _)| stable_hash(mv));
                 let (mv, child) = *rand.choose(&max_list).unwrap();
                 path.push((cur_node_id, mv.clone()));
 
@@ -119,7 +123,7 @@ pub(in super) fn montecarlo<G: Game + 'static>(nodes: &mut NodeList<G>, root: No
     let (num_sims, results) = {
         let cur_node = nodes.get_mut(cur_node_id);
 
-        thread_pool.simulate(cur_node.game.clone(), 25)
+        thread_pool.simulate(cur_node.game.clone(), config.leaf_budget, config.max_playout_moves)
     };
 
     // Backprop
@@ -134,8 +138,8 @@ pub(in super) fn montecarlo<G: Game + 'static>(nodes: &mut NodeList<G>, root: No
                 let mut child = nodes.get_mut(*child_id);
                 child.games += num_sims;
                 child.simulations += 1;
-                if let Some(&wins) = results.get(&child.player) {
-                    child.wins += wins;
+                for (player, reward) in results.iter() {
+                    *child.rewards.entry(player.clone()).or_insert(0.0) += *reward;
                 }
             } else {
                 // rare race condition; the node has been dropped so who cares
@@ -143,12 +147,14 @@ pub(in super) fn montecarlo<G: Game + 'static>(nodes: &mut NodeList<G>, root: No
         }
 
         if cur_node.weights.is_none() {
+            // max^n: each child is scored by the reward of the player choosing between them
+            let player = cur_node.player.clone();
             let total_games = cur_node.games;
             for child_id in cur_node.children.values() {
                 let mut child = nodes.get_mut(*child_id);
                 if child.games != 0 {
                     // UCT
-                    child.score = (child.wins as f64 / child.games as f64) + EXPLORATION_FACTOR * ((total_games as f64).ln() / (child.games as f64)).sqrt();
+                    child.score = child.value_for(&player) + config.exploration * ((total_games as f64).ln() / (child.games as f64)).sqrt();
                 }
             }
         }
@@ -3,42 +3,66 @@ use std::thread;
 use std::sync::mpsc::{ channel, Sender, Receiver };
 use std::time::{ Instant, Duration };
 
-use rand::{ thread_rng, Rng };
-use num_cpus;
+use rand::{ Rng, XorShiftRng };
 
 use super::{ Game, MoveList };
+use super::game::stable_hash;
+use super::config::{ LeafBudget, worker_rng };
+
+fn budget_left(budget: LeafBudget, start: Instant, num_sims: u32) -> bool {
+    match budget {
+        LeafBudget::Millis(time) => start.elapsed() < Duration::from_millis(time),
+        LeafBudget::Playouts(playouts) => num_sims < playouts,
+    }
+}
+
+// Picks a move with probability proportional to its weight
+fn weighted_choice<'a, M>(mvs: &'a [(M, f64)], rand: &mut XorShiftRng) -> Option<&'a M> {
+    let total: f64 = mvs.iter().map(|&(_, weight)| weight).sum();
+    let n = rand.next_f64() * total;
+    let mut sum = 0.0;
+    for &(ref mv, weight) in mvs.iter() {
+        sum += weight;
+        if sum >= n {
+            return Some(mv);
+        }
+    }
+    mvs.last().map(|&(ref mv, _)| mv)
+}
 
 pub(in super) struct SimThreadPool<G: Game> {
-    senders: Vec<Sender<(G, u64)>>,
-    receivers: Vec<Receiver<(u32, HashMap<G::Player, u32>)>>,
+    senders: Vec<Sender<(G, LeafBudget, Option<u32>)>>,
+    receivers: Vec<Receiver<(u32, HashMap<G::Player, f64>)>>,
 }
 
 impl<G> SimThreadPool<G> where G: Game + 'static {
-    pub fn new() -> Self {
-        let (senders, receivers) = (0..num_cpus::get())
-            .map(|_| {
-                let (to_thread, from_outside) = channel::<(G, u64)>();
-                let (to_outside, from_thread) = channel::<(u32, HashMap<G::Player, u32>)>();
+    pub fn new(workers: usize, seed: u64) -> Self {
+        let (senders, receivers) = (0..workers.max(1))
+            .map(|worker| {
+                let (to_thread, from_outside) = channel::<(G, LeafBudget, Option<u32>)>();
+                let (to_outside, from_thread) = channel::<(u32, HashMap<G::Player, f64>)>();
 
                 thread::spawn(move || {
-                    let mut rand = thread_rng();
+                    let mut rand = worker_rng(seed, worker);
 
-                    loop {
-                        let (game, time) = from_outside.recv().unwrap();
+                    // stops once the pool is dropped
+                    while let Ok((game, budget, max_moves)) = from_outside.recv() {
                         let start = Instant::now();
-                        let time_limit = Duration::from_millis(time);
 
                         let mut num_sims = 0;
-                        let mut results: HashMap<G::Player, u32> = HashMap::new();
-                        while start.elapsed() < time_limit {
+                        let mut results: HashMap<G::Player, f64> = HashMap::new();
+                        while budget_left(budget, start, num_sims) {
                             num_sims += 1;
 
                             let mut g = game.clone();
 
-                            while g.get_winner().is_none() {
+                            let mut moves_made = 0;
+                            while g.get_winner().is_none() && max_moves.map_or(true, |max| moves_made < max) {
+                                moves_made += 1;
                                 let moves = { g.available_moves() };
                                 match moves {
-                                    MoveList::Choice(mvs) => {
+                                    MoveList::Choice(mut mvs) => {
+                                        mvs.sort_by_key(|mv| stable_hash(mv));
                                         if let Some(mv) = rand.choose(&mvs) {
                                             g.make_move(mv);
                                         } else {
@@ -46,8 +70,9 @@ impl<G> SimThreadPool<G> where G: Game + 'static {
                                             break;
                                         }
                                     }
-                                    MoveList::Random(mvs) => {
-                                        if let Some(&(ref mv, _)) = rand.choose(&mvs) {
+                                    MoveList::Random(mut mvs) => {
+                                        mvs.sort_by_key(|&(ref mv, _)| stable_hash(mv));
+                                        if let Some(mv) = weighted_choice(&mvs, &mut rand) {
                                             g.make_move(mv);
                                         } else {
                                             // no possible moves
@@ -58,9 +83,8 @@ impl<G> SimThreadPool<G> where G: Game + 'static {
                                 
                             }
 
-                            if let Some(winner) = g.get_winner() {
-                                let new_val = { results.get(&winner) }.unwrap_or(&0) + 1;
-                                results.insert(winner, new_val);
+                            for (player, reward) in g.get_rewards() {
+                                *results.entry(player).or_insert(0.0) += reward;
                             }
                         }
 
@@ -77,20 +101,21 @@ impl<G> SimThreadPool<G> where G: Game + 'static {
         }
     }
 
-    pub fn simulate(&self, game: G, time_limit: u64) -> (u32, HashMap<G::Player, u32>) {
+    // Runs playouts on every worker, returning how many there were and the total reward for
+    // each player
+    pub fn simulate(&self, game: G, budget: LeafBudget, max_moves: Option<u32>) -> (u32, HashMap<G::Player, f64>) {
         for thread in self.senders.iter() {
-            thread.send((game.clone(), time_limit)).unwrap();
+            thread.send((game.clone(), budget, max_moves)).unwrap();
         }
 
-        let mut results: HashMap<G::Player, u32> = HashMap::new();
+        let mut results: HashMap<G::Player, f64> = HashMap::new();
         let mut num_sims = 0;
         for thread in self.receivers.iter() {
             let (thread_num_sims, thread_results) = thread.recv().unwrap();
 
             num_sims += thread_num_sims;
-            for (player, thread_wins) in thread_results.iter() {
-                let new_val = { results.get(player) }.unwrap_or(&0) + *thread_wins;
-                results.insert(player.clone(), new_val);
+            for (player, thread_reward) in thread_results.iter() {
+                *results.entry(player.clone()).or_insert(0.0) += *thread_reward;
             }
         }
 
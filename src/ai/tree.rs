@@ -21,7 +21,7 @@ pub(in super) struct MoveTreeNode<G: Game> {
 
 	pub score: f64,
 	pub games: u32,
-	pub wins: u32,
+	pub rewards: HashMap<G::Player, f64>, // summed over every game played through this node
 	pub simulations: u32,
 	
 	pub children: HashMap<G::Move, NodeID>,
@@ -37,11 +37,19 @@ impl<G> MoveTreeNode<G> where G: Game {
 
 			score: INFINITY,
 			games: 0,
-			wins: 0,
+			rewards: HashMap::new(),
 			simulations: 0,
 
 			children: HashMap::new(),
 			weights: None,
 		}
 	}
+
+	// The average reward for a player, which is their win rate when every playout has a winner
+	pub fn value_for(&self, player: &G::Player) -> f64 {
+		if self.games == 0 {
+			return 0.0;
+		}
+		self.rewards.get(player).cloned().unwrap_or(0.0) / self.games as f64
+	}
 }
\ No newline at end of file
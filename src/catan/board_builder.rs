@@ -16,6 +16,7 @@ pub struct BoardBuilder {
 	last_player_id: PlayerID,
 
 	pub first_player: PlayerID,
+	pub rules: CatanRules,
 }
 
 fn add_edge_to_vertex(edge: EdgeID, vertex: &mut VertexStatic) {
@@ -45,9 +46,79 @@ impl BoardBuilder {
 			last_player_id: 0,
 
 			first_player: 0,
+			rules: CatanRules::base_game(),
 		}
 	}
 
+	// The 30 hex board from the 5-6 player extension, set up with the extension's rules
+	pub fn extension() -> Self {
+		let mut builder = BoardBuilder::new();
+		builder.set_rules(CatanRules::five_six_player());
+
+		builder.add_hex(HexCoord::new(-4,  0), HexType::Water, 0);
+		builder.add_hex(HexCoord::new(-4,  1), HexType::Port3to1(5), 0);
+		builder.add_hex(HexCoord::new(-4,  2), HexType::Water, 0);
+		builder.add_hex(HexCoord::new(-4,  3), HexType::Port2to1(Resource::Sheep, 5), 0);
+
+		builder.add_hex(HexCoord::new(-3, -1), HexType::Port2to1(Resource::Wood, 1), 0);
+		builder.add_hex(HexCoord::new(-3,  0), HexType::Land(Resource::Wheat), 9);
+		builder.add_hex(HexCoord::new(-3,  1), HexType::Land(Resource::Brick), 4);
+		builder.add_hex(HexCoord::new(-3,  2), HexType::Land(Resource::Sheep), 8);
+		builder.add_hex(HexCoord::new(-3,  3), HexType::Water, 0);
+
+		builder.add_hex(HexCoord::new(-2, -2), HexType::Water, 0);
+		builder.add_hex(HexCoord::new(-2, -1), HexType::Land(Resource::Wheat), 2);
+		builder.add_hex(HexCoord::new(-2,  0), HexType::Land(Resource::Brick), 10);
+		builder.add_hex(HexCoord::new(-2,  1), HexType::Land(Resource::Rock), 11);
+		builder.add_hex(HexCoord::new(-2,  2), HexType::Land(Resource::Brick), 5);
+		builder.add_hex(HexCoord::new(-2,  3), HexType::Port2to1(Resource::Wheat, 5), 0);
+
+		builder.add_hex(HexCoord::new(-1, -3), HexType::Port3to1(1), 0);
+		builder.add_hex(HexCoord::new(-1, -2), HexType::Land(Resource::Wood), 9);
+		builder.add_hex(HexCoord::new(-1, -1), HexType::Land(Resource::Sheep), 11);
+		builder.add_hex(HexCoord::new(-1,  0), HexType::Land(Resource::Sheep), 6);
+		builder.add_hex(HexCoord::new(-1,  1), HexType::Land(Resource::Wheat), 3);
+		builder.add_hex(HexCoord::new(-1,  2), HexType::Desert, 0);
+		builder.add_hex(HexCoord::new(-1,  3), HexType::Water, 0);
+
+		builder.add_hex(HexCoord::new( 0, -4), HexType::Water, 0);
+		builder.add_hex(HexCoord::new( 0, -3), HexType::Land(Resource::Wood), 8);
+		builder.add_hex(HexCoord::new( 0, -2), HexType::Land(Resource::Rock), 12);
+		builder.add_hex(HexCoord::new( 0, -1), HexType::Land(Resource::Wheat), 2);
+		builder.add_hex(HexCoord::new( 0,  0), HexType::Land(Resource::Wood), 5);
+		builder.add_hex(HexCoord::new( 0,  1), HexType::Land(Resource::Wheat), 4);
+		builder.add_hex(HexCoord::new( 0,  2), HexType::Land(Resource::Wood), 8);
+		builder.add_hex(HexCoord::new( 0,  3), HexType::Port3to1(4), 0);
+
+		builder.add_hex(HexCoord::new( 1, -4), HexType::Port2to1(Resource::Brick, 2), 0);
+		builder.add_hex(HexCoord::new( 1, -3), HexType::Land(Resource::Rock), 9);
+		builder.add_hex(HexCoord::new( 1, -2), HexType::Land(Resource::Wood), 3);
+		builder.add_hex(HexCoord::new( 1, -1), HexType::Land(Resource::Rock), 6);
+		builder.add_hex(HexCoord::new( 1,  0), HexType::Land(Resource::Sheep), 3);
+		builder.add_hex(HexCoord::new( 1,  1), HexType::Land(Resource::Wheat), 10);
+		builder.add_hex(HexCoord::new( 1,  2), HexType::Water, 0);
+
+		builder.add_hex(HexCoord::new( 2, -4), HexType::Water, 0);
+		builder.add_hex(HexCoord::new( 2, -3), HexType::Land(Resource::Brick), 11);
+		builder.add_hex(HexCoord::new( 2, -2), HexType::Land(Resource::Rock), 5);
+		builder.add_hex(HexCoord::new( 2, -1), HexType::Land(Resource::Wood), 12);
+		builder.add_hex(HexCoord::new( 2,  0), HexType::Land(Resource::Sheep), 6);
+		builder.add_hex(HexCoord::new( 2,  1), HexType::Port2to1(Resource::Rock, 4), 0);
+
+		builder.add_hex(HexCoord::new( 3, -4), HexType::Port3to1(2), 0);
+		builder.add_hex(HexCoord::new( 3, -3), HexType::Desert, 0);
+		builder.add_hex(HexCoord::new( 3, -2), HexType::Land(Resource::Sheep), 10);
+		builder.add_hex(HexCoord::new( 3, -1), HexType::Land(Resource::Brick), 4);
+		builder.add_hex(HexCoord::new( 3,  0), HexType::Water, 0);
+
+		builder.add_hex(HexCoord::new( 4, -4), HexType::Water, 0);
+		builder.add_hex(HexCoord::new( 4, -3), HexType::Port2to1(Resource::Sheep, 3), 0);
+		builder.add_hex(HexCoord::new( 4, -2), HexType::Water, 0);
+		builder.add_hex(HexCoord::new( 4, -1), HexType::Port3to1(3), 0);
+
+		builder
+	}
+
 	fn get_edge(&mut self, pos: HexCoord, n: usize, vertices: [VertexID; 6]) -> EdgeID {
 		let neighbor_pos = &pos + &HEX_DIRECTIONS[n];
 
@@ -128,13 +199,18 @@ impl BoardBuilder {
 		self.hexes.insert(pos, hex);
 	}
 
+	pub fn set_rules(&mut self, rules: CatanRules) {
+		self.rules = rules;
+	}
+
 	pub fn add_player(&mut self, color: [f64; 3]) -> PlayerID {
+		self.last_player_id += 1;
 		let player = PlayerStatic {
+			id: self.last_player_id,
 			color,
 			next_player: 0,
 			prev_player: 0,
 		};
-		self.last_player_id += 1;
 		self.players.insert(self.last_player_id, player);
 		self.last_player_id
 	}
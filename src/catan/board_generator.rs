@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::fmt::{ self, Display, Formatter };
+
+use rand::Rng;
+
+use ai::seeded_rng;
+use catan::*;
+
+// Fairness rules for generated boards
+#[derive(Clone, Copy, Debug)]
+pub struct BoardConstraints {
+	pub no_adjacent_6_8: bool, // 6s and 8s can't be next to each other
+	pub no_adjacent_same_number: bool,
+	pub max_cluster: u8, // the most hexes of one resource that may touch each other
+}
+
+impl BoardConstraints {
+	// The usual advice for a balanced beginner-friendly board
+	pub fn official() -> Self {
+		BoardConstraints {
+			no_adjacent_6_8: true,
+			no_adjacent_same_number: true,
+			max_cluster: 2,
+		}
+	}
+
+	pub fn none() -> Self {
+		BoardConstraints {
+			no_adjacent_6_8: false,
+			no_adjacent_same_number: false,
+			max_cluster: 19,
+		}
+	}
+}
+
+// Shuffles to try before deciding the constraints can't be met
+const MAX_ATTEMPTS: u32 = 10_000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GenerationError {
+	Terrain, // no shuffle kept the resource clusters small enough
+	Numbers, // no shuffle kept the number tokens apart
+}
+
+impl Display for GenerationError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match *self {
+			GenerationError::Terrain => write!(f, "no terrain layout within {} tries meets the cluster limit", MAX_ATTEMPTS),
+			GenerationError::Numbers => write!(f, "no number layout within {} tries keeps the numbers apart", MAX_ATTEMPTS),
+		}
+	}
+}
+
+const TERRAIN: [(HexType, u8); 6] = [
+	(HexType::Land(Resource::Wood),  4),
+	(HexType::Land(Resource::Sheep), 4),
+	(HexType::Land(Resource::Wheat), 4),
+	(HexType::Land(Resource::Brick), 3),
+	(HexType::Land(Resource::Rock),  3),
+	(HexType::Desert,                1),
+];
+
+const NUMBERS: [u8; 18] = [2, 3, 3, 4, 4, 5, 5, 6, 6, 8, 8, 9, 9, 10, 10, 11, 11, 12];
+
+// where the harbors go around the standard board, and which side of each faces the land
+const HARBOR_SLOTS: [(i16, i16, u8); 9] = [
+	(-3,  1, 0), (-3,  3, 5), (-2, -1, 0),
+	(-1,  3, 4), ( 0, -3, 1), ( 1,  2, 4),
+	( 2, -3, 2), ( 3, -2, 2), ( 3,  0, 3),
+];
+
+// None is a 3:1 harbor
+const HARBORS: [Option<Resource>; 9] = [
+	None, None, None, None,
+	Some(Resource::Wheat), Some(Resource::Sheep), Some(Resource::Brick), Some(Resource::Wood), Some(Resource::Rock),
+];
+
+fn distance_from_center(pos: HexCoord) -> i16 {
+	let (q, r) = (pos.q(), pos.r());
+	q.abs().max(r.abs()).max((q + r).abs())
+}
+
+// Every hex within `radius` of the center, always in the same order
+fn hexes_within(radius: i16) -> Vec<HexCoord> {
+	let mut hexes = Vec::new();
+	for q in -radius..radius + 1 {
+		for r in -radius..radius + 1 {
+			let pos = HexCoord::new(q, r);
+			if distance_from_center(pos) <= radius {
+				hexes.push(pos);
+			}
+		}
+	}
+	hexes
+}
+
+// The size of the biggest group of touching hexes that produce the same resource
+fn largest_cluster(layout: &HashMap<HexCoord, HexType>) -> u8 {
+	let mut seen = Vec::new();
+	let mut largest = 0;
+	for (pos, typ) in layout.iter() {
+		if seen.contains(pos) || *typ == HexType::Desert {
+			continue;
+		}
+
+		let mut size = 0;
+		let mut open_set = vec![*pos];
+		seen.push(*pos);
+		while let Some(cur) = open_set.pop() {
+			size += 1;
+			for neighbor in cur.neighbors().iter() {
+				if layout.get(neighbor) == Some(typ) && !seen.contains(neighbor) {
+					seen.push(*neighbor);
+					open_set.push(*neighbor);
+				}
+			}
+		}
+		largest = largest.max(size);
+	}
+	largest
+}
+
+fn numbers_are_fair(rolls: &HashMap<HexCoord, u8>, constraints: &BoardConstraints) -> bool {
+	rolls.iter().all(|(pos, roll)| {
+		pos.neighbors().iter().all(|neighbor| {
+			match rolls.get(neighbor) {
+				Some(other) => {
+					let red = |n: u8| n == 6 || n == 8;
+					!(constraints.no_adjacent_6_8 && red(*roll) && red(*other)) &&
+						!(constraints.no_adjacent_same_number && roll == other)
+				}
+				None => true,
+			}
+		})
+	})
+}
+
+impl BoardBuilder {
+	pub fn random(seed: u64) -> Result<Self, GenerationError> {
+		BoardBuilder::random_with(seed, BoardConstraints::official())
+	}
+
+	// Shuffles the terrain, number tokens and harbors of the standard board, reshuffling
+	// until the layout satisfies `constraints`. The same seed always gives the same board.
+	pub fn random_with(seed: u64, constraints: BoardConstraints) -> Result<Self, GenerationError> {
+		let mut rng = seeded_rng(seed);
+
+		let land = hexes_within(2);
+		let mut terrain = Vec::new();
+		for &(typ, num) in TERRAIN.iter() {
+			for _ in 0..num {
+				terrain.push(typ);
+			}
+		}
+
+		let mut layout = None;
+		for _ in 0..MAX_ATTEMPTS {
+			rng.shuffle(&mut terrain);
+			let attempt: HashMap<HexCoord, HexType> = land.iter().cloned().zip(terrain.iter().cloned()).collect();
+			if largest_cluster(&attempt) <= constraints.max_cluster {
+				layout = Some(attempt);
+				break;
+			}
+		}
+		let layout = layout.ok_or(GenerationError::Terrain)?;
+
+		let mut numbers = NUMBERS.to_vec();
+		let mut rolls = None;
+		for _ in 0..MAX_ATTEMPTS {
+			rng.shuffle(&mut numbers);
+			let attempt: HashMap<HexCoord, u8> = land.iter().cloned()
+				.filter(|pos| layout[pos] != HexType::Desert)
+				.zip(numbers.iter().cloned())
+				.collect();
+			if numbers_are_fair(&attempt, &constraints) {
+				rolls = Some(attempt);
+				break;
+			}
+		}
+		let rolls = rolls.ok_or(GenerationError::Numbers)?;
+
+		let mut harbors = HARBORS.to_vec();
+		rng.shuffle(&mut harbors);
+		let mut ports = HashMap::new();
+		for (&(q, r, side), harbor) in HARBOR_SLOTS.iter().zip(harbors) {
+			ports.insert(HexCoord::new(q, r), match harbor {
+				Some(resource) => HexType::Port2to1(resource, side),
+				None => HexType::Port3to1(side),
+			});
+		}
+
+		let mut builder = BoardBuilder::new();
+		for pos in hexes_within(3) {
+			if let Some(typ) = layout.get(&pos) {
+				builder.add_hex(pos, *typ, *rolls.get(&pos).unwrap_or(&0));
+			} else {
+				builder.add_hex(pos, *ports.get(&pos).unwrap_or(&HexType::Water), 0);
+			}
+		}
+		Ok(builder)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn land_of(builder: &BoardBuilder) -> (HashMap<HexCoord, HexType>, HashMap<HexCoord, u8>) {
+		let mut layout = HashMap::new();
+		let mut rolls = HashMap::new();
+		for (pos, hex) in builder.hexes.iter() {
+			match hex.typ {
+				HexType::Land(_) | HexType::Desert => {
+					layout.insert(*pos, hex.typ);
+					if hex.typ != HexType::Desert {
+						rolls.insert(*pos, hex.roll);
+					}
+				}
+				_ => { }
+			}
+		}
+		(layout, rolls)
+	}
+
+	#[test]
+	fn same_seed_gives_same_board() {
+		for seed in 0..20 {
+			let a = BoardBuilder::random(seed).unwrap();
+			let b = BoardBuilder::random(seed).unwrap();
+			assert_eq!(a.to_layout(), b.to_layout());
+		}
+		assert!(BoardBuilder::random(1).unwrap().to_layout() != BoardBuilder::random(2).unwrap().to_layout());
+	}
+
+	#[test]
+	fn generated_boards_meet_the_constraints() {
+		let constraints = BoardConstraints::official();
+		for seed in 0..50 {
+			let builder = BoardBuilder::random_with(seed, constraints).unwrap();
+			let (layout, rolls) = land_of(&builder);
+
+			assert_eq!(layout.len(), 19);
+			assert_eq!(rolls.len(), 18);
+			assert!(largest_cluster(&layout) <= constraints.max_cluster);
+			assert!(numbers_are_fair(&rolls, &constraints));
+
+			let mut numbers: Vec<u8> = rolls.values().cloned().collect();
+			numbers.sort();
+			assert_eq!(numbers, NUMBERS.to_vec());
+
+			let ports = builder.hexes.values().filter(|hex| match hex.typ {
+				HexType::Port2to1(..) | HexType::Port3to1(_) => true,
+				_ => false,
+			}).count();
+			assert_eq!(ports, HARBORS.len());
+		}
+	}
+
+	#[test]
+	fn impossible_constraints_are_an_error() {
+		let no_clusters = BoardConstraints { max_cluster: 0, ..BoardConstraints::none() };
+		assert_eq!(BoardBuilder::random_with(0, no_clusters).err(), Some(GenerationError::Terrain));
+	}
+}
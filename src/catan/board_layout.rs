@@ -0,0 +1,413 @@
+// A plain text format for boards, so maps can be shared without writing Rust. Each line
+// is one entry, and lines starting with '#' are comments:
+//
+//   hex 0 0 desert
+//   hex 1 0 wood 6
+//   hex 2 0 gold 9
+//   hex 3 0 water
+//   hex 3 -1 port3 3
+//   hex 3 -2 port2 wheat 2
+//   player #ff0000
+//   player #00ff00
+//   order 2 1
+//
+// Land hexes give their roll number, and harbors give the side of the hex that faces land.
+// Players are numbered from 1 in the order they're listed, and `order` is optional.
+
+use std::fmt::{ self, Display, Formatter };
+use std::fs::File;
+use std::io::{ self, Read, Write };
+
+use catan::*;
+
+#[derive(Debug)]
+pub enum LayoutError {
+	Io(io::Error),
+	Malformed { line: usize, message: String },
+	DuplicateHex { line: usize, pos: HexCoord },
+	InvalidPortSide { line: usize, side: String },
+	InvalidOrder { line: usize, message: String },
+}
+
+impl Display for LayoutError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match *self {
+			LayoutError::Io(ref err) => write!(f, "{}", err),
+			LayoutError::Malformed { line, ref message } => write!(f, "line {}: {}", line, message),
+			LayoutError::DuplicateHex { line, pos } => write!(f, "line {}: hex {:?} is already on the board", line, pos),
+			LayoutError::InvalidPortSide { line, ref side } => write!(f, "line {}: {} is not a side that faces land", line, side),
+			LayoutError::InvalidOrder { line, ref message } => write!(f, "line {}: {}", line, message),
+		}
+	}
+}
+
+impl From<io::Error> for LayoutError {
+	fn from(err: io::Error) -> Self {
+		LayoutError::Io(err)
+	}
+}
+
+pub(in super) fn resource_name(resource: Resource) -> &'static str {
+	match resource {
+		Resource::Wheat => "wheat",
+		Resource::Sheep => "sheep",
+		Resource::Brick => "brick",
+		Resource::Wood  => "wood",
+		Resource::Rock  => "rock",
+		Resource::Paper => "paper",
+		Resource::Cloth => "cloth",
+		Resource::Coin  => "coin",
+	}
+}
+
+// Only the five base resources can appear on the board
+pub(in super) fn parse_resource(name: &str) -> Option<Resource> {
+	match name {
+		"wheat" => Some(Resource::Wheat),
+		"sheep" => Some(Resource::Sheep),
+		"brick" => Some(Resource::Brick),
+		"wood"  => Some(Resource::Wood),
+		"rock"  => Some(Resource::Rock),
+		_ => None,
+	}
+}
+
+pub(in super) fn parse_color(text: &str) -> Option<[f64; 3]> {
+	if text.len() != 7 || !text.starts_with('#') {
+		return None;
+	}
+	let mut color = [0.0; 3];
+	for i in 0..3 {
+		match u8::from_str_radix(&text[1 + 2*i .. 3 + 2*i], 16) {
+			Ok(byte) => color[i] = byte as f64 / 255.0,
+			Err(_) => return None,
+		}
+	}
+	Some(color)
+}
+
+fn color_to_string(color: [f64; 3]) -> String {
+	let byte = |c: f64| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+	format!("#{:02x}{:02x}{:02x}", byte(color[0]), byte(color[1]), byte(color[2]))
+}
+
+pub(in super) fn malformed(line: usize, message: String) -> LayoutError {
+	LayoutError::Malformed { line, message }
+}
+
+pub(in super) fn parse_number<T: ::std::str::FromStr>(line: usize, what: &str, text: Option<&str>) -> Result<T, LayoutError> {
+	match text {
+		Some(text) => text.parse().map_err(|_| malformed(line, format!("{} should be a number, not '{}'", what, text))),
+		None => Err(malformed(line, format!("missing {}", what))),
+	}
+}
+
+fn parse_side(line: usize, text: Option<&str>) -> Result<u8, LayoutError> {
+	let text = text.ok_or_else(|| malformed(line, "missing port side".to_string()))?;
+	match text.parse() {
+		Ok(side) if side < 6 => Ok(side),
+		_ => Err(LayoutError::InvalidPortSide { line, side: text.to_string() }),
+	}
+}
+
+// Writes out hexes as (position, type, roll) and players as (id, color, next player)
+pub(in super) fn write_layout(mut hexes: Vec<(HexCoord, HexType, u8)>, mut players: Vec<(PlayerID, [f64; 3], PlayerID)>,
+                              first_player: PlayerID) -> String {
+	let mut out = String::new();
+
+	hexes.sort_by_key(|&(pos, _, _)| (pos.q(), pos.r()));
+	for (pos, typ, roll) in hexes {
+		let desc = match typ {
+			HexType::Water => "water".to_string(),
+			HexType::Desert => "desert".to_string(),
+			HexType::Gold => format!("gold {}", roll),
+			HexType::Land(resource) => format!("{} {}", resource_name(resource), roll),
+			HexType::Port3to1(side) => format!("port3 {}", side),
+			HexType::Port2to1(resource, side) => format!("port2 {} {}", resource_name(resource), side),
+		};
+		out.push_str(&format!("hex {} {} {}\n", pos.q(), pos.r(), desc));
+	}
+
+	players.sort_by_key(|&(id, _, _)| id);
+	if !players.is_empty() {
+		out.push('\n');
+	}
+	for &(_, color, _) in players.iter() {
+		out.push_str(&format!("player {}\n", color_to_string(color)));
+	}
+
+	// walk around the table from the first player; ids are renumbered from 1 when loaded
+	let number = |id: PlayerID| players.iter().position(|&(other, _, _)| other == id).map(|n| n + 1);
+	let next_player = |id: PlayerID| players.iter().find(|&&(other, _, _)| other == id).map(|&(_, _, next)| next);
+	if let Some(first) = number(first_player) {
+		let mut order = vec![first];
+		let mut next = next_player(first_player);
+		while let Some(id) = next {
+			if id == first_player || order.len() >= players.len() {
+				break;
+			}
+			order.extend(number(id));
+			next = next_player(id);
+		}
+		let order: Vec<String> = order.iter().map(|n| n.to_string()).collect();
+		out.push_str(&format!("order {}\n", order.join(" ")));
+	}
+
+	out
+}
+
+impl BoardBuilder {
+	pub fn from_layout(text: &str) -> Result<Self, LayoutError> {
+		let mut builder = BoardBuilder::new();
+		let mut hexes = Vec::new(); // kept in file order so ids come out the same every time
+		let mut ports = Vec::new();
+		let mut players = Vec::new();
+		let mut order = None;
+
+		for (index, full_line) in text.lines().enumerate() {
+			let line = index + 1;
+			if full_line.trim_left().starts_with('#') {
+				continue;
+			}
+			let mut words = full_line.split_whitespace();
+
+			match words.next() {
+				None => continue,
+				Some("hex") => {
+					let q = parse_number(line, "q coordinate", words.next())?;
+					let r = parse_number(line, "r coordinate", words.next())?;
+					let pos = HexCoord::new(q, r);
+					if hexes.iter().any(|&(other, _, _)| other == pos) {
+						return Err(LayoutError::DuplicateHex { line, pos });
+					}
+
+					let (typ, roll) = match words.next() {
+						Some("water") => (HexType::Water, 0),
+						Some("desert") => (HexType::Desert, 0),
+						Some("gold") => (HexType::Gold, parse_number(line, "roll", words.next())?),
+						Some("port3") => {
+							let side = parse_side(line, words.next())?;
+							ports.push((line, pos, side));
+							(HexType::Port3to1(side), 0)
+						}
+						Some("port2") => {
+							let name = words.next().ok_or_else(|| malformed(line, "missing port resource".to_string()))?;
+							let resource = parse_resource(name).ok_or_else(|| malformed(line, format!("unknown resource '{}'", name)))?;
+							let side = parse_side(line, words.next())?;
+							ports.push((line, pos, side));
+							(HexType::Port2to1(resource, side), 0)
+						}
+						Some(name) => match parse_resource(name) {
+							Some(resource) => (HexType::Land(resource), parse_number(line, "roll", words.next())?),
+							None => return Err(malformed(line, format!("unknown hex type '{}'", name))),
+						},
+						None => return Err(malformed(line, "missing hex type".to_string())),
+					};
+					hexes.push((pos, typ, roll));
+				}
+				Some("player") => {
+					let text = words.next().ok_or_else(|| malformed(line, "missing player color".to_string()))?;
+					let color = parse_color(text).ok_or_else(|| malformed(line, format!("'{}' is not a color like #ff0000", text)))?;
+					players.push(builder.add_player(color));
+				}
+				Some("order") => {
+					let mut ids = Vec::new();
+					for word in words.by_ref() {
+						ids.push(parse_number::<usize>(line, "player number", Some(word))?);
+					}
+					order = Some((line, ids));
+				}
+				Some(word) => return Err(malformed(line, format!("unknown entry '{}'", word))),
+			}
+
+			if let Some(extra) = words.next() {
+				return Err(malformed(line, format!("unexpected '{}'", extra)));
+			}
+		}
+
+		// a harbor has to face a land hex, or nobody could ever use it
+		for &(line, pos, side) in ports.iter() {
+			let faces_land = hexes.iter().any(|&(other, typ, _)| {
+				other == pos.neighbor(side as usize) && match typ {
+					HexType::Desert | HexType::Land(_) | HexType::Gold => true,
+					_ => false,
+				}
+			});
+			if !faces_land {
+				return Err(LayoutError::InvalidPortSide { line, side: side.to_string() });
+			}
+		}
+
+		for (pos, typ, roll) in hexes {
+			builder.add_hex(pos, typ, roll);
+		}
+
+		// a game can't be started without anyone to play it
+		if players.is_empty() {
+			let line = order.as_ref().map_or(text.lines().count(), |&(line, _)| line);
+			return Err(LayoutError::InvalidOrder { line, message: "the board needs at least one player".to_string() });
+		}
+
+		match order {
+			Some((line, ids)) => {
+				let mut sorted = ids.clone();
+				sorted.sort();
+				sorted.dedup();
+				if ids.is_empty() || sorted.len() != ids.len() || sorted != players {
+					return Err(LayoutError::InvalidOrder { line, message: format!("order should list each of the {} players exactly once", players.len()) });
+				}
+				builder.set_player_order(ids);
+			}
+			None => builder.set_player_order(players),
+		}
+
+		Ok(builder)
+	}
+
+	pub fn load(path: &str) -> Result<Self, LayoutError> {
+		let mut text = String::new();
+		File::open(path)?.read_to_string(&mut text)?;
+		BoardBuilder::from_layout(&text)
+	}
+
+	pub fn to_layout(&self) -> String {
+		let hexes = self.hexes.iter().map(|(pos, hex)| (*pos, hex.typ, hex.roll)).collect();
+		let players = self.players.iter().map(|(id, player)| (*id, player.color, player.next_player)).collect();
+		write_layout(hexes, players, self.first_player)
+	}
+
+	pub fn save(&self, path: &str) -> Result<(), LayoutError> {
+		File::create(path)?.write_all(self.to_layout().as_bytes())?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const FIXTURE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/base_game.board");
+
+	const SMALL: &'static str = "hex 0 0 wheat 6\nhex 1 0 port3 3\nplayer #ff0000\nplayer #00ff00\n";
+
+	fn error_of(text: &str) -> LayoutError {
+		match BoardBuilder::from_layout(text) {
+			Ok(_) => panic!("layout should not load:\n{}", text),
+			Err(err) => err,
+		}
+	}
+
+	#[test]
+	fn loads_the_base_game() {
+		let builder = BoardBuilder::load(FIXTURE).unwrap();
+		assert_eq!(builder.hexes.len(), 37);
+		assert_eq!(builder.players.len(), 3);
+
+		let land = builder.hexes.values().filter(|hex| match hex.typ {
+			HexType::Land(_) | HexType::Desert => true,
+			_ => false,
+		}).count();
+		assert_eq!(land, 19);
+		assert!(builder.hexes[&HexCoord::new(0, -1)].typ == HexType::Land(Resource::Rock));
+		assert_eq!(builder.hexes[&HexCoord::new(0, -1)].roll, 6);
+		assert!(builder.hexes[&HexCoord::new(3, -2)].typ == HexType::Port2to1(Resource::Wheat, 2));
+
+		assert_eq!(builder.first_player, 1);
+		assert_eq!(builder.players[&1].next_player, 2);
+		assert_eq!(builder.players[&2].next_player, 3);
+		assert_eq!(builder.players[&3].next_player, 1);
+		assert_eq!(builder.players[&1].prev_player, 3);
+	}
+
+	#[test]
+	fn emitting_and_parsing_gives_the_same_board() {
+		let builder = BoardBuilder::load(FIXTURE).unwrap();
+		let text = builder.to_layout();
+		let reloaded = BoardBuilder::from_layout(&text).unwrap();
+		assert_eq!(reloaded.to_layout(), text);
+
+		for (pos, hex) in builder.hexes.iter() {
+			let other = &reloaded.hexes[pos];
+			assert!(other.typ == hex.typ && other.roll == hex.roll);
+		}
+		for (id, player) in builder.players.iter() {
+			let other = &reloaded.players[id];
+			assert_eq!((other.color, other.next_player, other.prev_player), (player.color, player.next_player, player.prev_player));
+		}
+		assert_eq!(reloaded.first_player, builder.first_player);
+
+		// a different first player survives the trip too
+		let mut rotated = BoardBuilder::from_layout(&format!("{}order 2 1\n", SMALL)).unwrap();
+		assert_eq!(rotated.first_player, 2);
+		rotated = BoardBuilder::from_layout(&rotated.to_layout()).unwrap();
+		assert_eq!(rotated.first_player, 2);
+		assert_eq!(rotated.players[&2].next_player, 1);
+	}
+
+	#[test]
+	fn duplicate_hexes_are_rejected() {
+		match error_of("hex 0 0 wheat 6\nhex 0 0 rock 8\nplayer #ff0000\n") {
+			LayoutError::DuplicateHex { line: 2, pos } => assert_eq!(pos, HexCoord::new(0, 0)),
+			err => panic!("wrong error: {}", err),
+		}
+	}
+
+	#[test]
+	fn bad_port_sides_are_rejected() {
+		// not a side at all
+		match error_of("hex 0 0 wheat 6\nhex 1 0 port3 6\nplayer #ff0000\n") {
+			LayoutError::InvalidPortSide { line: 2, ref side } => assert_eq!(side, "6"),
+			err => panic!("wrong error: {}", err),
+		}
+		// a side facing water
+		match error_of("hex 0 0 wheat 6\nhex 1 0 port2 rock 0\nplayer #ff0000\n") {
+			LayoutError::InvalidPortSide { line: 2, ref side } => assert_eq!(side, "0"),
+			err => panic!("wrong error: {}", err),
+		}
+		assert!(BoardBuilder::from_layout(SMALL).is_ok());
+	}
+
+	#[test]
+	fn bad_orders_are_rejected() {
+		let bad_orders = [
+			format!("{}order 1 1\n", SMALL),
+			format!("{}order 1\n", SMALL),
+			format!("{}order 1 2 3\n", SMALL),
+			format!("{}order\n", SMALL),
+		];
+		for text in bad_orders.iter() {
+			match error_of(text) {
+				LayoutError::InvalidOrder { line: 5, .. } => { }
+				err => panic!("wrong error: {}", err),
+			}
+		}
+
+		// no players at all, with or without an order line
+		match error_of("hex 0 0 wheat 6\norder\n") {
+			LayoutError::InvalidOrder { line: 2, .. } => { }
+			err => panic!("wrong error: {}", err),
+		}
+		match error_of("hex 0 0 wheat 6\n") {
+			LayoutError::InvalidOrder { line: 1, .. } => { }
+			err => panic!("wrong error: {}", err),
+		}
+	}
+
+	#[test]
+	fn malformed_lines_are_rejected() {
+		let bad_lines = [
+			"hex 0 wheat 6",
+			"hex 0 0 lava 6",
+			"hex 0 0 wheat six",
+			"hex 0 0 wheat 6 extra",
+			"player red",
+			"road 0 0",
+		];
+		for text in bad_lines.iter() {
+			match error_of(&format!("{}\nplayer #ff0000\n", text)) {
+				LayoutError::Malformed { line: 1, .. } => { }
+				err => panic!("wrong error for '{}': {}", text, err),
+			}
+		}
+	}
+}
@@ -1,5 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{ HashMap, HashSet };
 use std::cell::RefCell;
+use std::sync::Arc;
 use std::fmt::Debug;
 use std::hash::*;
 use std::f64::consts::PI;
@@ -17,6 +18,8 @@ use catan::edge::*;
 use catan::vertex::*;
 use catan::player::*;
 use catan::board_builder::*;
+use catan::rules::*;
+use catan::cities_knights::*;
 
 pub type EdgeID   = usize;
 pub type VertexID = usize;
@@ -24,10 +27,20 @@ pub type PlayerID = usize;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Resource {
-    Wheat, Sheep, Brick, Wood, Rock
+    Wheat, Sheep, Brick, Wood, Rock,
+    Paper, Cloth, Coin, // Cities & Knights commodities
 }
 const ALL_RESOURCES: [Resource; 5] = 
     [ Resource::Wheat, Resource::Sheep, Resource::Brick, Resource::Wood, Resource::Rock ];
+const ALL_COMMODITIES: [Resource; 3] =
+    [ Resource::Paper, Resource::Cloth, Resource::Coin ];
+const ALL_CARDS: [Resource; 8] = [
+    Resource::Wheat, Resource::Sheep, Resource::Brick, Resource::Wood, Resource::Rock,
+    Resource::Paper, Resource::Cloth, Resource::Coin,
+];
+
+// keeps the move tree from exploding with endless rounds of haggling
+pub(in super) const MAX_TRADE_OFFERS_PER_TURN: u8 = 3;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum DevCard {
@@ -44,10 +57,19 @@ pub enum GameState {
     SetupSettlementRoad { first_player: PlayerID, settlement: VertexID },
     SetupCities { first_player: PlayerID },
     SetupCityRoad { first_player: PlayerID, city: VertexID },
+    PreRoll, // the player may play a soldier before rolling
     Roll,
     Turn,
+    SpecialBuilding, // the current player may build during someone else's turn
     DrawingDevCard,
     ResolvingDevCard(DevCard, u8),
+    Discarding { roller: PlayerID },
+    ChoosingGold { roller: PlayerID },
+    PillagingCity { roller: PlayerID }, // the current player picks a city to lose to the barbarians
+    DrawingProgressCard { roller: PlayerID, track: Improvement },
+    ResolvingProgressCard(ProgressCard),
+    TradeOffered { proposer: PlayerID },
+    TradeCountered { responder: PlayerID },
     MovingRobber,
     StealingCards(PlayerID),
     GameOver,
@@ -65,14 +87,25 @@ pub enum CatanMove {
 
     // Special
     Roll(u8),
+    RollDice { red: u8, yellow: u8, event: EventDie }, // Cities & Knights
     EndTurn,
-    MoveRobber(HexCoord, PlayerID),
+    Discard(Vec<(Resource, u8)>),
+    ChooseGold(Vec<(Resource, u8)>),
+    MoveRobber(HexCoord, Option<PlayerID>), // where to, and who to steal from
+    MovePirate(HexCoord, Option<PlayerID>),
     Steal(Resource, PlayerID),
+    LoseCity(VertexID),
 
     // Building / Upgrading
     BuildRoad(EdgeID),
     BuildSettlement(VertexID),
     BuildCity(VertexID),
+    BuildShip(EdgeID),
+    BuildKnight(VertexID),
+    ActivateKnight(VertexID),
+    PromoteKnight(VertexID),
+    ChaseRobber(VertexID),
+    ImproveCity(Improvement),
 
     // Development Cards
     BuyDevCard,
@@ -81,7 +114,24 @@ pub enum CatanMove {
     ReceiveMonopoly(Resource),
     ReceiveYearOfPlenty(Resource, Resource),
 
+    // Progress Cards
+    DrawProgressCard(ProgressCard),
+    PlayProgressCard(ProgressCard),
+    ReceiveProgressMonopoly(Resource),
+
     // Trade
+    // give and take are always from the perspective of the player proposing the trade
+    OfferTrade {
+        to: PlayerID,
+        give: Vec<(Resource, u8)>,
+        take: Vec<(Resource, u8)>,
+    },
+    AcceptTrade,
+    RejectTrade,
+    CounterTrade {
+        give: Vec<(Resource, u8)>,
+        take: Vec<(Resource, u8)>,
+    },
     Trade {
         to: PlayerID,
         give: Vec<(Resource, u8)>,
@@ -115,15 +165,98 @@ lazy_static! {
         (CatanMove::Roll(11), 2.0 / 36.0),
         (CatanMove::Roll(12), 1.0 / 36.0),
     ];
-    static ref DEVELOPMENT_CARDS: Vec<(CatanMove, f64)> = vec![
-        (CatanMove::DrawDevCard(DevCard::Soldier),      14.0 / 25.0),
-        (CatanMove::DrawDevCard(DevCard::YearOfPlenty),  2.0 / 25.0),
-        (CatanMove::DrawDevCard(DevCard::Monopoly),      2.0 / 25.0),
-        (CatanMove::DrawDevCard(DevCard::RoadBuilding),  2.0 / 25.0),
-        (CatanMove::DrawDevCard(DevCard::VictoryPoint),  5.0 / 25.0),
-    ];
+
+    // Cities & Knights rolls the two dice separately, plus the event die
+    static ref EVENT_ROLLS: Vec<(CatanMove, f64)> = {
+        let mut rolls = Vec::new();
+        for red in 1..7 {
+            for yellow in 1..7 {
+                for &(event, chance) in EVENT_DIE.iter() {
+                    rolls.push((CatanMove::RollDice { red, yellow, event }, chance / 36.0));
+                }
+            }
+        }
+        rolls
+    };
+}
+
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub(in super) struct TradeOffer {
+    pub(in super) from: PlayerID,
+    pub(in super) to: PlayerID,
+    pub(in super) give: Vec<(Resource, u8)>,
+    pub(in super) take: Vec<(Resource, u8)>,
+}
+
+// What everything costs to build or buy
+pub(in super) const ROAD_COST: [(Resource, u8); 2] = [(Resource::Wood, 1), (Resource::Brick, 1)];
+pub(in super) const SHIP_COST: [(Resource, u8); 2] = [(Resource::Wood, 1), (Resource::Sheep, 1)];
+pub(in super) const SETTLEMENT_COST: [(Resource, u8); 4] =
+    [(Resource::Wood, 1), (Resource::Brick, 1), (Resource::Sheep, 1), (Resource::Wheat, 1)];
+pub(in super) const CITY_COST: [(Resource, u8); 2] = [(Resource::Wheat, 2), (Resource::Rock, 3)];
+pub(in super) const DEV_CARD_COST: [(Resource, u8); 3] = [(Resource::Sheep, 1), (Resource::Wheat, 1), (Resource::Rock, 1)];
+pub(in super) const KNIGHT_COST: [(Resource, u8); 2] = [(Resource::Sheep, 1), (Resource::Rock, 1)]; // also to promote one
+pub(in super) const ACTIVATE_COST: [(Resource, u8); 1] = [(Resource::Wheat, 1)];
+
+// Moves cards from a player's hand back into the bank
+fn pay_bank(player: &mut Player, bank: &mut [u8; 8], resource: Resource, num: u8) {
+    player.consume_resource(resource, num);
+    bank[resource as usize] += num;
+}
+
+// Moves cards from the bank into a player's hand
+fn take_from_bank(player: &mut Player, bank: &mut [u8; 8], resource: Resource, num: u8) {
+    bank[resource as usize] -= num;
+    player.give_resource(resource, num);
+}
+
+pub(in super) fn has_cards(player: &Player, cards: &[(Resource, u8)]) -> bool {
+    cards.iter().all(|&(res, num)| player.get_resource(res) >= num)
 }
 
+fn pay_cost(player: &mut Player, bank: &mut [u8; 8], cost: &[(Resource, u8)]) {
+    for &(resource, num) in cost.iter() {
+        pay_bank(player, bank, resource, num);
+    }
+}
+
+// Number of cards of each resource in the player's hand, in ALL_CARDS order
+fn hand_counts(player: &Player) -> [u8; 8] {
+    let mut counts = [0; 8];
+    for resource in ALL_CARDS.iter() {
+        counts[*resource as usize] = player.get_resource(*resource);
+    }
+    counts
+}
+
+// Every way of choosing `num` cards out of `available` (indexed like ALL_CARDS)
+fn card_combinations(available: &[u8; 8], num: u8) -> Vec<Vec<(Resource, u8)>> {
+    fn combinations(available: &[u8; 8], resources: &[Resource], num: u8,
+                    cur: &mut Vec<(Resource, u8)>, out: &mut Vec<Vec<(Resource, u8)>>) {
+        if num == 0 {
+            out.push(cur.clone());
+            return;
+        }
+
+        if let Some((&resource, rest)) = resources.split_first() {
+            let max = available[resource as usize].min(num);
+            for n in 0..max + 1 {
+                if n > 0 {
+                    cur.push((resource, n));
+                }
+                combinations(available, rest, num - n, cur, out);
+                if n > 0 {
+                    cur.pop();
+                }
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    combinations(available, &ALL_CARDS, num, &mut Vec::new(), &mut out);
+    out
+}
 
 #[derive(Clone)]
 pub struct Catan {
@@ -131,15 +264,39 @@ pub struct Catan {
     pub(in super) edges:    HashMap<EdgeID,   Edge>,
     pub(in super) vertices: HashMap<VertexID, Vertex>,
     pub(in super) players:  HashMap<PlayerID, RefCell<Player>>,
-
-    cur_player: PlayerID,
-    state: GameState,
-
-    robber_pos: HexCoord,
-    largest_army_owner: Option<PlayerID>,
-    longest_road: Option<(PlayerID, u8)>,
-
-    new_dev_cards: Vec<DevCard>, // cards the player has drawn but can't use yet
+    pub(in super) rules: Arc<CatanRules>,
+    pub(in super) first_player: PlayerID, // who sat down first; seats go round from here
+
+    pub(in super) cur_player: PlayerID,
+    pub(in super) state: GameState,
+    pub(in super) winner: Option<PlayerID>,
+
+    pub(in super) robber_pos: HexCoord,
+    pub(in super) pirate_pos: Option<HexCoord>, // Seafarers; None until the pirate is first moved
+    pub(in super) bank: [u8; 8], // indexed by resource, in ALL_CARDS order
+    pub(in super) largest_army_owner: Option<PlayerID>,
+    pub(in super) longest_road: Option<(PlayerID, u8)>,
+
+    pub(in super) new_dev_cards: Vec<DevCard>, // cards the player has drawn but can't use yet
+    pub(in super) dev_deck: [(DevCard, u8); 5], // cards that haven't been drawn yet
+    pub(in super) dev_card_played: bool, // only one development card may be played per turn
+    pub(in super) has_rolled: bool,
+    pub(in super) special_building: Option<PlayerID>, // whose turn the special building phase follows
+
+    pub(in super) pending_trade: Option<TradeOffer>,
+    pub(in super) trades_offered: u8, // offers the current player has made this turn
+
+    pub(in super) gold_owed: Vec<(PlayerID, u8)>, // players still choosing what to take for gold fields
+    pub(in super) islands: Arc<HashMap<VertexID, usize>>, // which island each vertex is on
+    pub(in super) home_islands: HashSet<usize>, // islands settled during setup, which don't earn a bonus
+
+    pub(in super) barbarian_pos: u8, // steps the barbarian ship has taken towards Catan
+    pub(in super) barbarians_attacked: bool,
+    pub(in super) metropolises: [Option<PlayerID>; 3], // indexed by Improvement
+    pub(in super) progress_decks: [Vec<(ProgressCard, u8)>; 3], // indexed by Improvement
+    pub(in super) pending_roll: Option<u8>, // paid out once the event die has been dealt with
+    pub(in super) pillaged: Vec<PlayerID>, // players still choosing a city to lose to the barbarians
+    pub(in super) progress_draws: Vec<(PlayerID, Improvement)>, // players still drawing progress cards
 }
 
 impl Catan {
@@ -157,16 +314,46 @@ impl Catan {
             edges:    HashMap::new(),
             vertices: HashMap::new(),
             players:  HashMap::new(),
+            rules: Arc::new(builder.rules),
+            first_player: builder.first_player,
 
             cur_player: builder.first_player,
             state: GameState::SetupSettlements { first_player: builder.first_player },
+            winner: None,
 
             robber_pos,
+            pirate_pos: None,
+            bank: [0; 8],
             largest_army_owner: None,
             longest_road: None,
 
             new_dev_cards: Vec::new(),
+            dev_deck: builder.rules.dev_deck,
+            dev_card_played: false,
+            has_rolled: false,
+            special_building: None,
+
+            pending_trade: None,
+            trades_offered: 0,
+
+            gold_owed: Vec::new(),
+            islands: Arc::new(HashMap::new()),
+            home_islands: HashSet::new(),
+
+            barbarian_pos: 0,
+            barbarians_attacked: false,
+            metropolises: [None; 3],
+            progress_decks: progress_decks(),
+            pending_roll: None,
+            pillaged: Vec::new(),
+            progress_draws: Vec::new(),
         };
+        for resource in ALL_RESOURCES.iter() {
+            catan.bank[*resource as usize] = catan.rules.bank_size;
+        }
+        for commodity in ALL_COMMODITIES.iter() {
+            catan.bank[*commodity as usize] = catan.rules.commodity_bank_size;
+        }
 
         for (pos, hex) in builder.hexes.drain() {
             /* println!("Hex {:?}:\n  Edges: {:?}\n  Vertices: {:?}",
@@ -188,9 +375,10 @@ impl Catan {
             ); */
             catan.vertices.insert(id, Vertex::new(vertex));
         }
+        catan.islands = Arc::new(catan.find_islands());
 
         for (id, player) in builder.players.drain() {
-            catan.players.insert(id, RefCell::new(Player::new(player)));
+            catan.players.insert(id, RefCell::new(Player::new(player, &catan.rules)));
         }
 
         catan
@@ -208,15 +396,27 @@ impl Catan {
         self.edges.get(id)
     }
 
-    fn is_water(&self, hex_coord: HexCoord) -> bool {
+    pub fn get_rules(&self) -> &CatanRules {
+        &self.rules
+    }
+
+    pub fn get_bank_resource(&self, resource: Resource) -> u8 {
+        self.bank[resource as usize]
+    }
+
+    pub fn dev_cards_left(&self) -> u8 {
+        self.dev_deck.iter().map(|&(_, num)| num).sum()
+    }
+
+    pub(in super) fn is_water(&self, hex_coord: HexCoord) -> bool {
         let hex = self.hexes.get(&hex_coord).unwrap();
         match hex.static_data.typ {
             HexType::Water | HexType::Port2to1(_, _) | HexType::Port3to1(_) => true,
-            HexType::Desert | HexType::Land(_) => false,
+            HexType::Desert | HexType::Land(_) | HexType::Gold => false,
         }
     }
 
-    fn is_bridge(&self, edge_id: EdgeID) -> bool {
+    pub(in super) fn is_bridge(&self, edge_id: EdgeID) -> bool {
         let edge = self.edges.get(&edge_id).unwrap();
         match (edge.static_data.hexes[0], edge.static_data.hexes[1]) {
             (None, None) => true,
@@ -227,7 +427,72 @@ impl Catan {
         }
     }
 
-    fn settlement_location_is_valid(&self, vertex_id: VertexID) -> bool {
+    // Ships can't be built on land or next to the pirate
+    pub(in super) fn ship_allowed(&self, edge_id: EdgeID) -> bool {
+        let edge = self.edges.get(&edge_id).unwrap();
+        let mut at_sea = false;
+        for hex_opt in edge.static_data.hexes.iter() {
+            match *hex_opt {
+                Some(hex) if Some(hex) == self.pirate_pos => return false,
+                Some(hex) if !self.is_water(hex) => { }
+                _ => at_sea = true,
+            }
+        }
+        at_sea
+    }
+
+    // Numbers each group of connected land hexes and works out which one each vertex is on
+    fn find_islands(&self) -> HashMap<VertexID, usize> {
+        let mut hex_islands = HashMap::new();
+        let mut num_islands = 0;
+        for pos in self.hexes.keys() {
+            if self.is_water(*pos) || hex_islands.contains_key(pos) {
+                continue;
+            }
+
+            num_islands += 1;
+            let mut open_set = vec![*pos];
+            while let Some(cur) = open_set.pop() {
+                hex_islands.insert(cur, num_islands);
+                for neighbor in cur.neighbors().iter() {
+                    if self.hexes.contains_key(neighbor) && !self.is_water(*neighbor) && !hex_islands.contains_key(neighbor) {
+                        open_set.push(*neighbor);
+                    }
+                }
+            }
+        }
+
+        let mut vertex_islands = HashMap::new();
+        for (vertex_id, vertex) in self.vertices.iter() {
+            for hex in vertex.static_data.hexes.iter().filter_map(|x| *x) {
+                if let Some(island) = hex_islands.get(&hex) {
+                    vertex_islands.insert(*vertex_id, *island);
+                    break;
+                }
+            }
+        }
+        vertex_islands
+    }
+
+    // Settlements placed during setup mark their island as home; after that, the first
+    // settlement each player builds on any other island is worth bonus points
+    fn check_island_bonus(&mut self, vertex_id: VertexID) {
+        if let Some(&island) = self.islands.get(&vertex_id) {
+            match self.state {
+                GameState::SetupSettlements { .. } | GameState::SetupCities { .. } => {
+                    self.home_islands.insert(island);
+                }
+                _ => {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    if !self.home_islands.contains(&island) && player.islands.insert(island) {
+                        player.victory_points += self.rules.island_bonus;
+                    }
+                }
+            }
+        }
+    }
+
+    pub(in super) fn settlement_location_is_valid(&self, vertex_id: VertexID) -> bool {
         let vertex = self.vertices.get(&vertex_id).unwrap();
 
         let mut has_land = false;
@@ -256,7 +521,7 @@ impl Catan {
                     edge.static_data.vertices[0]
                 };
                 let vertex_2 = self.vertices.get(&vertex_id_2).unwrap();
-                if vertex_2.structure.is_some() {
+                if vertex_2.building_owner().is_some() {
                     return false;
                 }
             }
@@ -264,7 +529,392 @@ impl Catan {
         true
     }
 
-    fn check_for_port(&self, vertex_id: VertexID, player_id: PlayerID) {
+    // With the friendly robber rule, the robber can't be placed next to another player
+    // who has only a couple of (public) points
+    pub(in super) fn robber_allowed(&self, hex_pos: &HexCoord) -> bool {
+        if !self.rules.friendly_robber {
+            return true;
+        }
+
+        let hex = self.hexes.get(hex_pos).unwrap();
+        hex.static_data.vertices.iter().all(|vertex_id| {
+            match self.vertices.get(vertex_id).unwrap().building_owner() {
+                Some(player_id) if player_id != self.cur_player => {
+                    let player = self.players.get(&player_id).unwrap().borrow();
+                    player.victory_points > self.rules.friendly_robber_points
+                }
+                _ => true,
+            }
+        })
+    }
+
+    // Roads, settlements, cities and development cards the player can afford
+    fn build_moves(&self, player: &Player, moves: &mut Vec<CatanMove>) {
+        let (buildable_edges, buildable_vertices) = player.get_buildable_spaces(&self);
+
+        if has_cards(player, &ROAD_COST) && player.roads_left > 0 {
+            // road
+
+            for edge in &buildable_edges {
+                moves.push(CatanMove::BuildRoad(*edge));
+            }
+        }
+
+        if has_cards(player, &SETTLEMENT_COST) && player.settlements_left > 0 {
+            // settlement
+
+            for vertex in &buildable_vertices {
+                if self.settlement_location_is_valid(*vertex) {
+                    moves.push(CatanMove::BuildSettlement(*vertex));
+                }
+            }
+        }
+
+        if has_cards(player, &CITY_COST) && player.cities_left > 0 {
+            // city
+
+            for vertex in player.settlements.iter() {
+                moves.push(CatanMove::BuildCity(*vertex));
+            }
+        }
+
+        if self.rules.seafarers {
+            let (ship_edges, ship_vertices) = player.get_buildable_ship_spaces(&self);
+
+            if has_cards(player, &SHIP_COST) && player.ships_left > 0 {
+                // ship
+
+                for edge in &ship_edges {
+                    moves.push(CatanMove::BuildShip(*edge));
+                }
+            }
+
+            if has_cards(player, &SETTLEMENT_COST) && player.settlements_left > 0 {
+                // settlement at the end of a ship
+
+                for vertex in ship_vertices.difference(&buildable_vertices) {
+                    if self.settlement_location_is_valid(*vertex) {
+                        moves.push(CatanMove::BuildSettlement(*vertex));
+                    }
+                }
+            }
+        }
+
+        if self.rules.cities_knights {
+            self.knight_moves(player, &buildable_vertices, moves);
+        }
+
+        if has_cards(player, &DEV_CARD_COST) && self.dev_cards_left() > 0 {
+            // development card
+
+            moves.push(CatanMove::BuyDevCard);
+        }
+    }
+
+    // The robber can move to any land hex except the one it's on. If anyone else has built
+    // next to that hex, one of them gets robbed.
+    fn robber_moves(&self, friendly: bool) -> Vec<CatanMove> {
+        let mut mvs = Vec::new();
+        for (pos, hex) in self.hexes.iter() {
+            if *pos == self.robber_pos || self.is_water(*pos) || (friendly && !self.robber_allowed(pos)) {
+                continue;
+            }
+
+            let mut victims = Vec::new();
+            for vertex_id in hex.static_data.vertices.iter() {
+                let vertex = self.vertices.get(&vertex_id).unwrap();
+                if let Some(player) = vertex.building_owner() {
+                    if player != self.cur_player && !victims.contains(&player) {
+                        victims.push(player);
+                    }
+                }
+            }
+
+            if victims.len() == 0 {
+                mvs.push(CatanMove::MoveRobber(*pos, None));
+            } else {
+                for player in victims {
+                    mvs.push(CatanMove::MoveRobber(*pos, Some(player)));
+                }
+            }
+        }
+        mvs
+    }
+
+    // The pirate can move to any water hex except the one it's on, and robs someone with a
+    // ship next to it
+    fn pirate_moves(&self) -> Vec<CatanMove> {
+        let mut mvs = Vec::new();
+        for (pos, hex) in self.hexes.iter() {
+            if Some(*pos) == self.pirate_pos || !self.is_water(*pos) {
+                continue;
+            }
+
+            let mut victims = Vec::new();
+            for edge_id in hex.static_data.edges.iter() {
+                if let Some(player) = self.edges.get(edge_id).unwrap().ship {
+                    if player != self.cur_player && !victims.contains(&player) {
+                        victims.push(player);
+                    }
+                }
+            }
+
+            if victims.len() == 0 {
+                mvs.push(CatanMove::MovePirate(*pos, None));
+            } else {
+                for player in victims {
+                    mvs.push(CatanMove::MovePirate(*pos, Some(player)));
+                }
+            }
+        }
+        mvs
+    }
+
+    // Pays out resources for a roll, or starts discarding and moving the robber on a 7.
+    // Returns the state the game should move to.
+    fn resolve_roll(&mut self, roll: u8) -> GameState {
+        let roller = self.cur_player;
+        if roll == 7 {
+            if let Some(player_id) = self.next_discarding_player(roller, roller) {
+                self.cur_player = player_id;
+                return GameState::Discarding { roller };
+            }
+            return self.robber_state();
+        }
+
+        let mut payouts = Vec::new();
+        let mut demand = [0u8; 8];
+        let mut gold = HashMap::new();
+
+        for (hex_pos, hex) in self.hexes.iter() {
+            if hex.static_data.roll == roll && *hex_pos != self.robber_pos {
+                let resource = match hex.static_data.typ {
+                    HexType::Land(resource) => Some(resource),
+                    HexType::Gold => None,
+                    _ => continue,
+                };
+
+                for vertex_id in hex.static_data.vertices.iter() {
+                    let vertex = self.vertices.get(vertex_id).unwrap();
+                    if let Some((structure, player_id)) = vertex.structure {
+                        let num = match structure {
+                            Structure::Settlement => 1,
+                            Structure::City => 2,
+                            Structure::Knight { .. } => continue,
+                        };
+                        if let Some(resource) = resource {
+                            let commodity = match structure {
+                                Structure::City if self.rules.cities_knights => city_commodity(resource),
+                                _ => None,
+                            };
+                            if let Some(commodity) = commodity {
+                                payouts.push((player_id, resource, 1));
+                                payouts.push((player_id, commodity, 1));
+                                demand[resource as usize] += 1;
+                                demand[commodity as usize] += 1;
+                            } else {
+                                payouts.push((player_id, resource, num));
+                                demand[resource as usize] += num;
+                            }
+                        } else {
+                            *gold.entry(player_id).or_insert(0) += num;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (player_id, resource, num) in payouts {
+            // if the bank can't pay everyone, nobody gets that resource
+            if demand[resource as usize] <= self.bank[resource as usize] {
+                let mut player = self.players.get(&player_id).unwrap().borrow_mut();
+                take_from_bank(&mut player, &mut self.bank, resource, num);
+            }
+        }
+
+        // players on gold fields pick their resources in turn order
+        let mut player_id = roller;
+        loop {
+            if let Some(&num) = gold.get(&player_id) {
+                self.gold_owed.push((player_id, num));
+            }
+            player_id = self.players.get(&player_id).unwrap().borrow().static_data.next_player;
+            if player_id == roller {
+                break;
+            }
+        }
+
+        if let Some(&(player_id, _)) = self.gold_owed.first() {
+            self.cur_player = player_id;
+            GameState::ChoosingGold { roller }
+        } else {
+            GameState::Turn
+        }
+    }
+
+    // In Cities & Knights the robber stays put until the barbarians have attacked once
+    fn robber_state(&self) -> GameState {
+        if self.rules.cities_knights && !self.barbarians_attacked {
+            GameState::Turn
+        } else {
+            GameState::MovingRobber
+        }
+    }
+
+    // Works through the event die (pillaged cities, then progress cards) before paying out
+    // the roll that was put aside in `pending_roll`
+    fn continue_roll(&mut self, roller: PlayerID) -> GameState {
+        if let Some(&player_id) = self.pillaged.first() {
+            self.cur_player = player_id;
+            return GameState::PillagingCity { roller };
+        }
+
+        // skip anyone who would draw from an empty deck
+        while let Some(&(_, track)) = self.progress_draws.first() {
+            if self.progress_decks[track as usize].iter().any(|&(_, num)| num > 0) {
+                break;
+            }
+            self.progress_draws.remove(0);
+        }
+        if let Some(&(player_id, track)) = self.progress_draws.first() {
+            self.cur_player = player_id;
+            return GameState::DrawingProgressCard { roller, track };
+        }
+
+        self.cur_player = roller;
+        match self.pending_roll.take() {
+            Some(roll) => self.resolve_roll(roll),
+            None => GameState::Turn,
+        }
+    }
+
+    // The barbarians are as strong as the number of cities on the board. If the active
+    // knights can't hold them off, the weakest defenders each lose a city; otherwise the
+    // strongest defender becomes the Defender of Catan. Either way the knights go home.
+    fn barbarian_attack(&mut self) {
+        let mut strength = 0;
+        let mut cities = HashMap::new();
+        let mut defense = HashMap::new();
+        for vertex in self.vertices.values() {
+            match vertex.structure {
+                Some((Structure::City, player_id)) => {
+                    strength += 1;
+                    *cities.entry(player_id).or_insert(0) += 1;
+                }
+                Some((Structure::Knight { level, active: true }, player_id)) => {
+                    *defense.entry(player_id).or_insert(0) += level;
+                }
+                _ => { }
+            }
+        }
+        let total_defense: u8 = defense.values().sum();
+
+        let roller = self.cur_player;
+        if total_defense < strength {
+            // metropolises can't be pillaged
+            let exposed: Vec<PlayerID> = self.players.keys().cloned().filter(|player_id| {
+                let metropolises = self.metropolises.iter().filter(|owner| **owner == Some(*player_id)).count();
+                *cities.get(player_id).unwrap_or(&0) > metropolises
+            }).collect();
+            let weakest = exposed.iter().map(|player_id| *defense.get(player_id).unwrap_or(&0)).min();
+
+            let mut player_id = roller;
+            loop {
+                if exposed.contains(&player_id) && Some(*defense.get(&player_id).unwrap_or(&0)) == weakest {
+                    self.pillaged.push(player_id);
+                }
+                player_id = self.players.get(&player_id).unwrap().borrow().static_data.next_player;
+                if player_id == roller {
+                    break;
+                }
+            }
+        } else {
+            // a tie for the strongest defense normally earns progress cards, which is left out
+            let best = defense.values().cloned().max().unwrap_or(0);
+            let defenders: Vec<PlayerID> = defense.iter()
+                .filter(|&(_, strength)| *strength == best)
+                .map(|(player_id, _)| *player_id)
+                .collect();
+            if defenders.len() == 1 {
+                self.players.get(&defenders[0]).unwrap().borrow_mut().victory_points += 1;
+            }
+        }
+
+        for vertex in self.vertices.values_mut() {
+            if let Some((Structure::Knight { level, .. }, player_id)) = vertex.structure {
+                vertex.structure = Some((Structure::Knight { level, active: false }, player_id));
+            }
+        }
+        self.barbarian_pos = 0;
+        self.barbarians_attacked = true;
+    }
+
+    // Knights, knight upgrades and city improvements the player can afford
+    fn knight_moves(&self, player: &Player, buildable_vertices: &HashSet<VertexID>, moves: &mut Vec<CatanMove>) {
+        if has_cards(player, &KNIGHT_COST) && player.knights_left[0] > 0 {
+            for vertex in buildable_vertices {
+                moves.push(CatanMove::BuildKnight(*vertex));
+            }
+        }
+
+        // boards without a desert can start with the robber off the board
+        let robber_vertices = self.hexes.get(&self.robber_pos).map(|hex| &hex.static_data.vertices);
+        for (vertex_id, vertex) in self.vertices.iter() {
+            if let Some((Structure::Knight { level, active }, owner)) = vertex.structure {
+                if owner != self.cur_player {
+                    continue;
+                }
+
+                if !active && has_cards(player, &ACTIVATE_COST) {
+                    moves.push(CatanMove::ActivateKnight(*vertex_id));
+                }
+
+                // mighty knights need a level 3 politics improvement
+                let can_promote = level < 2 || (level < 3 && player.improvements[Improvement::Politics as usize] >= 3);
+                if can_promote && has_cards(player, &KNIGHT_COST) && player.knights_left[level as usize] > 0 {
+                    moves.push(CatanMove::PromoteKnight(*vertex_id));
+                }
+
+                // not during the special building phase
+                let can_chase = self.state == GameState::Turn && self.barbarians_attacked;
+                let next_to_robber = robber_vertices.map_or(false, |vertices| vertices.contains(vertex_id));
+                if active && can_chase && next_to_robber {
+                    moves.push(CatanMove::ChaseRobber(*vertex_id));
+                }
+            }
+        }
+
+        // improvements need a city to go in
+        if player.cities_left < self.rules.max_cities {
+            for track in ALL_IMPROVEMENTS.iter() {
+                let level = player.improvements[*track as usize];
+                if level < MAX_IMPROVEMENT_LEVEL && player.get_resource(track.commodity()) >= level + 1 {
+                    moves.push(CatanMove::ImproveCity(*track));
+                }
+            }
+        }
+    }
+
+    // Gives the player 2 of `resource` for every matching hex next to their buildings
+    fn harvest(&mut self, resource: Resource) {
+        let mut num = 0;
+        for hex in self.hexes.values() {
+            if hex.static_data.typ == HexType::Land(resource) {
+                let adjacent = hex.static_data.vertices.iter().any(|vertex_id| {
+                    self.vertices.get(vertex_id).unwrap().building_owner() == Some(self.cur_player)
+                });
+                if adjacent {
+                    num += 2;
+                }
+            }
+        }
+
+        let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+        let num = num.min(self.bank[resource as usize]);
+        take_from_bank(&mut player, &mut self.bank, resource, num);
+    }
+
+    pub(in super) fn check_for_port(&self, vertex_id: VertexID, player_id: PlayerID) {
         let vertex = self.vertices.get(&vertex_id).unwrap();
         let mut player = self.players.get(&player_id).unwrap().borrow_mut();
         for hex_opt in vertex.static_data.hexes.iter() {
@@ -283,31 +933,99 @@ impl Catan {
         }
     }
 
-    fn check_longest_road(&mut self, new_road: EdgeID) -> Option<GameState> {
-        let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-        let road_len = player.get_longest_road(self, new_road);
+    // The player whose turn it is, which isn't always the player making the current decision
+    fn turn_player(&self) -> PlayerID {
+        if let Some(player_id) = self.special_building {
+            return player_id;
+        }
 
-        if road_len >= 5 {
-            if let Some((old_owner, old_road_len)) = self.longest_road {
-                if old_owner != self.cur_player {
-                    self.longest_road = Some((self.cur_player, road_len));
-                    self.players.get(&old_owner).unwrap().borrow_mut().victory_points -= 2;
+        match self.state {
+            GameState::Discarding { roller } => roller,
+            GameState::ChoosingGold { roller } => roller,
+            GameState::TradeOffered { proposer } => proposer,
+            GameState::PillagingCity { roller } => roller,
+            GameState::DrawingProgressCard { roller, .. } => roller,
+            _ => self.cur_player,
+        }
+    }
 
-                    player.victory_points += 2;
-                    if player.victory_points >= 10 {
-                        return Some(GameState::GameOver);
-                    }
-                }
+    // Where to go once the robber has been dealt with; a soldier can be played before rolling
+    fn after_robber_state(&self) -> GameState {
+        if self.has_rolled {
+            GameState::Turn
+        } else {
+            GameState::Roll
+        }
+    }
+
+    // Finds the first player, going around the table from `from`, who has too many cards
+    // and must discard. Stops once we get back around to the player who rolled.
+    fn next_discarding_player(&self, roller: PlayerID, from: PlayerID) -> Option<PlayerID> {
+        let mut player_id = from;
+        loop {
+            let player = self.players.get(&player_id).unwrap().borrow();
+            if player.num_cards() > self.rules.hand_limit {
+                return Some(player_id);
+            }
+
+            player_id = player.static_data.next_player;
+            if player_id == roller {
+                return None;
+            }
+        }
+    }
+
+    fn exchange(&self, from_id: PlayerID, to_id: PlayerID, give: &[(Resource, u8)], take: &[(Resource, u8)]) {
+        let mut player = self.players.get(&from_id).unwrap().borrow_mut();
+        let mut other_player = self.players.get(&to_id).unwrap().borrow_mut();
+        for &(res, num) in give {
+            player.consume_resource(res, num);
+            other_player.give_resource(res, num);
+        }
+        for &(res, num) in take {
+            player.give_resource(res, num);
+            other_player.consume_resource(res, num);
+        }
+    }
+
+    // Recomputes every player's longest road and hands the longest road card (and its 2 VP)
+    // to whoever deserves it. The holder keeps it on a tie; if the holder's road is broken
+    // and several players tie for the longest road, the card is set aside until one of
+    // them pulls ahead.
+    fn update_longest_road(&mut self) {
+        let lengths: Vec<(PlayerID, u8)> = self.players.iter().map(|(id, player)| {
+            (*id, player.borrow().get_longest_road(self, *id))
+        }).collect();
+        let max_len = lengths.iter().map(|&(_, len)| len).max().unwrap_or(0);
+
+        let old_owner = self.longest_road.map(|(owner, _)| owner);
+        let new_owner = if max_len < 5 {
+            None
+        } else {
+            let leaders: Vec<PlayerID> = lengths.iter()
+                .filter(|&&(_, len)| len == max_len)
+                .map(|&(id, _)| id)
+                .collect();
+
+            if old_owner.map_or(false, |owner| leaders.contains(&owner)) {
+                old_owner
+            } else if leaders.len() == 1 {
+                Some(leaders[0])
             } else {
-                self.longest_road = Some((self.cur_player, road_len));
+                None
+            }
+        };
 
-                player.victory_points += 2;
-                if player.victory_points >= 10 {
-                    return Some(GameState::GameOver);
-                }
+        self.longest_road = new_owner.map(|owner| (owner, max_len));
+
+        if new_owner != old_owner {
+            if let Some(owner) = old_owner {
+                self.players.get(&owner).unwrap().borrow_mut().victory_points -= 2;
+            }
+            if let Some(owner) = new_owner {
+                self.players.get(&owner).unwrap().borrow_mut().victory_points += 2;
             }
         }
-        None
     }
 
     pub fn draw(&self, ctx: &Context, w: f64, h: f64) {
@@ -329,6 +1047,15 @@ impl Catan {
         ctx.arc(offx + HEX_SCALE / 3.0, offy + HEX_SCALE / 3.0, HEX_SCALE / 5.0, 0.0, 2.0*PI);
         ctx.set_source_rgb(0.0, 0.0, 0.0);
         ctx.fill();
+
+        if let Some(pirate_pos) = self.pirate_pos {
+            let (offx, offy) = pirate_pos.to_point();
+            ctx.arc(offx - HEX_SCALE / 3.0, offy + HEX_SCALE / 3.0, HEX_SCALE / 5.0, 0.0, 2.0*PI);
+            ctx.set_source_rgb(0.2, 0.2, 0.2);
+            ctx.fill_preserve();
+            ctx.set_source_rgb(1.0, 1.0, 1.0);
+            ctx.stroke();
+        }
     }
 
     pub fn get_player_color(&self, player: PlayerID) -> [f64; 3] {
@@ -353,11 +1080,34 @@ impl Hash for Catan {
 
         self.cur_player.hash(state);
         self.state.hash(state);
+        self.winner.hash(state);
         self.robber_pos.hash(state);
+        self.pirate_pos.hash(state);
+        self.bank.hash(state);
         
         for card in self.new_dev_cards.iter() {
             card.hash(state);
         }
+        self.dev_deck.hash(state);
+        self.dev_card_played.hash(state);
+        self.has_rolled.hash(state);
+        self.special_building.hash(state);
+
+        let mut home_islands: Vec<&usize> = self.home_islands.iter().collect();
+        home_islands.sort();
+        home_islands.hash(state);
+
+        self.pending_trade.hash(state);
+        self.trades_offered.hash(state);
+        self.gold_owed.hash(state);
+
+        self.barbarian_pos.hash(state);
+        self.barbarians_attacked.hash(state);
+        self.metropolises.hash(state);
+        self.progress_decks.hash(state);
+        self.pending_roll.hash(state);
+        self.pillaged.hash(state);
+        self.progress_draws.hash(state);
     }
 }
 
@@ -382,7 +1132,7 @@ impl Game for Catan {
                 MoveList::Choice(vertex.static_data.edges.iter().filter_map(|edge_opt| {
                     edge_opt.and_then(|edge_id| {
                         let edge = self.edges.get(&edge_id).unwrap();
-                        if edge.road.is_none() {
+                        if edge.is_empty() && !self.is_bridge(edge_id) {
                             Some(CatanMove::PlaceRoad(edge_id))
                         } else {
                             None
@@ -391,10 +1141,15 @@ impl Game for Catan {
                 }).collect())
             }
             GameState::SetupCities { first_player } => {
+                // second round of setup; a settlement unless the rules say to start with a city
                 let mut mvs = Vec::new();
                 for (vertex_id, vertex) in self.vertices.iter() {
                     if vertex.structure.is_none() && self.settlement_location_is_valid(*vertex_id) {
-                        mvs.push(CatanMove::PlaceCity(*vertex_id));
+                        if self.rules.setup_city {
+                            mvs.push(CatanMove::PlaceCity(*vertex_id));
+                        } else {
+                            mvs.push(CatanMove::PlaceSettlement(*vertex_id));
+                        }
                     }
                 }
                 MoveList::Choice(mvs)
@@ -404,7 +1159,7 @@ impl Game for Catan {
                 MoveList::Choice(vertex.static_data.edges.iter().filter_map(|edge_opt| {
                     edge_opt.and_then(|edge_id| {
                         let edge = self.edges.get(&edge_id).unwrap();
-                        if edge.road.is_none() {
+                        if edge.is_empty() && !self.is_bridge(edge_id) {
                             Some(CatanMove::PlaceRoad(edge_id))
                         } else {
                             None
@@ -412,31 +1167,116 @@ impl Game for Catan {
                     })
                 }).collect())
             }
+            GameState::PreRoll => {
+                let mut mvs = vec![CatanMove::SetState(GameState::Roll)];
+                if !self.dev_card_played && player.dev_cards.contains(&DevCard::Soldier) {
+                    mvs.push(CatanMove::PlayDevCard(DevCard::Soldier));
+                }
+                MoveList::Choice(mvs)
+            }
             GameState::Roll => {
-                MoveList::Random((*ROLLS).clone())
+                if self.rules.cities_knights {
+                    MoveList::Random((*EVENT_ROLLS).clone())
+                } else {
+                    MoveList::Random((*ROLLS).clone())
+                }
             }
-            GameState::MovingRobber => {
+            GameState::PillagingCity { .. } => {
                 let mut mvs = Vec::new();
-                for (pos, hex) in self.hexes.iter() {
-                    if hex.static_data.typ != HexType::Water {
-                        for vertex_id in hex.static_data.vertices.iter() {
-                            let vertex = self.vertices.get(&vertex_id).unwrap();
-
-                            if let Some((structure, player)) = vertex.structure {
-                                if player != self.cur_player {
-                                    match structure {
-                                        Structure::City | Structure::Settlement => {
-                                            mvs.push(CatanMove::MoveRobber(*pos, player))
-                                        }
-                                        _ => { }
-                                    }
-                                }
-                            }
+                for (vertex_id, vertex) in self.vertices.iter() {
+                    if let Some((Structure::City, owner)) = vertex.structure {
+                        if owner == self.cur_player {
+                            mvs.push(CatanMove::LoseCity(*vertex_id));
                         }
                     }
                 }
                 MoveList::Choice(mvs)
             }
+            GameState::DrawingProgressCard { track, .. } => {
+                MoveList::Random(self.progress_decks[track as usize].iter().filter_map(|&(card, num)| {
+                    if num > 0 {
+                        Some((CatanMove::DrawProgressCard(card), num as f64))
+                    } else {
+                        None
+                    }
+                }).collect())
+            }
+            GameState::ResolvingProgressCard(card) => {
+                let resources = match card {
+                    ProgressCard::ResourceMonopoly => &ALL_RESOURCES[..],
+                    ProgressCard::TradeMonopoly => &ALL_COMMODITIES[..],
+                    _ => panic!()
+                };
+                MoveList::Choice(resources.iter().map(|res| {
+                    CatanMove::ReceiveProgressMonopoly(*res)
+                }).collect())
+            }
+            GameState::Discarding { .. } => {
+                let num = player.num_cards() / 2;
+                MoveList::Choice(card_combinations(&hand_counts(&player), num).into_iter().map(|cards| {
+                    CatanMove::Discard(cards)
+                }).collect())
+            }
+            GameState::ChoosingGold { .. } => {
+                // take whatever is left if the bank can't cover everything
+                let owed = self.gold_owed.first().map(|&(_, num)| num).unwrap_or(0);
+                let mut available = self.bank;
+                for commodity in ALL_COMMODITIES.iter() {
+                    available[*commodity as usize] = 0;
+                }
+                let num = owed.min(available.iter().sum());
+                MoveList::Choice(card_combinations(&available, num).into_iter().map(|cards| {
+                    CatanMove::ChooseGold(cards)
+                }).collect())
+            }
+            GameState::TradeOffered { proposer } => {
+                let offer = self.pending_trade.as_ref().expect("No trade has been offered");
+                let mut mvs = vec![CatanMove::RejectTrade];
+
+                if has_cards(&player, &offer.take) {
+                    mvs.push(CatanMove::AcceptTrade);
+                }
+
+                // counter by asking the proposer for one more card
+                let from = self.players.get(&proposer).unwrap().borrow();
+                for resource in ALL_RESOURCES.iter() {
+                    if offer.take.iter().any(|entry| entry.0 == *resource) {
+                        continue;
+                    }
+
+                    let mut give = offer.give.clone();
+                    if let Some(entry) = give.iter_mut().find(|entry| entry.0 == *resource) {
+                        entry.1 += 1;
+                    }
+                    if give == offer.give {
+                        give.push((*resource, 1));
+                    }
+
+                    if has_cards(&from, &give) && has_cards(&player, &offer.take) {
+                        mvs.push(CatanMove::CounterTrade { give, take: offer.take.clone() });
+                    }
+                }
+
+                MoveList::Choice(mvs)
+            }
+            GameState::TradeCountered { responder } => {
+                let offer = self.pending_trade.as_ref().expect("No trade has been countered");
+                MoveList::Choice(vec![
+                    CatanMove::RejectTrade,
+                    CatanMove::Trade { to: responder, give: offer.give.clone(), take: offer.take.clone() },
+                ])
+            }
+            GameState::MovingRobber => {
+                let mut mvs = self.robber_moves(true);
+                if mvs.len() == 0 {
+                    // the friendly robber has nowhere to go, so it has to rob someone anyway
+                    mvs = self.robber_moves(false);
+                }
+                if self.rules.seafarers {
+                    mvs.extend(self.pirate_moves());
+                }
+                MoveList::Choice(mvs)
+            }
             GameState::StealingCards(from_id) => {
                 let from = self.players.get(&from_id).unwrap().borrow();
                 let mvs: Vec<(CatanMove, f64)> = from.cards.iter().filter_map(|pair| {
@@ -450,57 +1290,19 @@ impl Game for Catan {
                 if mvs.len() > 0 {
                     MoveList::Random(mvs)
                 } else {
-                    MoveList::Choice(vec![CatanMove::SetState(GameState::Turn)])
+                    MoveList::Choice(vec![CatanMove::SetState(self.after_robber_state())])
                 }
             }
             GameState::Turn => {
                 let mut moves = Vec::new();
                 moves.push(CatanMove::EndTurn);
 
-                let wood = player.get_resource(Resource::Wood);
-                let wheat = player.get_resource(Resource::Wheat);
-                let brick = player.get_resource(Resource::Brick);
-                let rock = player.get_resource(Resource::Rock);
-                let sheep = player.get_resource(Resource::Sheep);
-
-                let (buildable_edges, buildable_vertices) = player.get_buildable_spaces(&self);
-
-                if wood >= 1 && brick >= 1 {
-                    // road
-
-                    for edge in &buildable_edges {
-                        moves.push(CatanMove::BuildRoad(*edge));
-                    }
-                }
-
-                if wood >= 1 && wheat >= 1 && brick >= 1 && sheep >= 1 {
-                    // settlement
-
-                    for vertex in &buildable_vertices {
-                        if self.settlement_location_is_valid(*vertex) {
-                            moves.push(CatanMove::BuildSettlement(*vertex));
-                        }
-                    }
-                }
-
-                if rock >= 3 && wheat >= 2 {
-                    // city
-
-                    for vertex in player.settlements.iter() {
-                        moves.push(CatanMove::BuildCity(*vertex));
-                    }
-                }
-
-                if sheep >= 1 && wheat >=1 && rock >= 1 {
-                    // development card
-
-                    moves.push(CatanMove::BuyDevCard);
-                }
+                self.build_moves(&player, &mut moves);
 
                 for (resource, num) in player.cards.iter() {
                     if *num >= 4 && !player.has_3to1_port {
                         for other_resource in ALL_RESOURCES.iter() {
-                            if *other_resource != *resource {
+                            if *other_resource != *resource && self.get_bank_resource(*other_resource) > 0 {
                                 moves.push(CatanMove::Convert4x {
                                     from: *resource,
                                     to: *other_resource
@@ -511,7 +1313,7 @@ impl Game for Catan {
 
                     if *num >= 3 && player.has_3to1_port {
                         for other_resource in ALL_RESOURCES.iter() {
-                            if *other_resource != *resource {
+                            if *other_resource != *resource && self.get_bank_resource(*other_resource) > 0 {
                                 moves.push(CatanMove::Convert3x {
                                     from: *resource,
                                     to: *other_resource
@@ -522,7 +1324,7 @@ impl Game for Catan {
 
                     if *num >= 2 && player.ports.contains(resource) {
                         for other_resource in ALL_RESOURCES.iter() {
-                            if *other_resource != *resource {
+                            if *other_resource != *resource && self.get_bank_resource(*other_resource) > 0 {
                                 moves.push(CatanMove::Convert2x {
                                     from: *resource,
                                     to: *other_resource
@@ -532,14 +1334,59 @@ impl Game for Catan {
                     }
                 }
 
-                for card in player.dev_cards.iter() {
-                    moves.push(CatanMove::PlayDevCard(*card));
+                if !self.dev_card_played {
+                    for card in player.dev_cards.iter() {
+                        if *card != DevCard::VictoryPoint {
+                            moves.push(CatanMove::PlayDevCard(*card));
+                        }
+                    }
+                }
+
+                for card in player.progress_cards.iter() {
+                    moves.push(CatanMove::PlayProgressCard(*card));
                 }
 
+                if self.trades_offered < MAX_TRADE_OFFERS_PER_TURN {
+                    // offer one of our cards for one of theirs
+                    for (other_id, other_cell) in self.players.iter() {
+                        if *other_id == self.cur_player {
+                            continue;
+                        }
+
+                        let other_player = other_cell.borrow();
+                        for give in ALL_RESOURCES.iter() {
+                            if player.get_resource(*give) == 0 {
+                                continue;
+                            }
+                            for take in ALL_RESOURCES.iter() {
+                                if *take != *give && other_player.get_resource(*take) > 0 {
+                                    moves.push(CatanMove::OfferTrade {
+                                        to: *other_id,
+                                        give: vec![(*give, 1)],
+                                        take: vec![(*take, 1)],
+                                    });
+                                }
+                            }
+                        }
+                    }
+                }
+
+                MoveList::Choice(moves)
+            }
+            GameState::SpecialBuilding => {
+                // no trading or playing development cards, just building
+                let mut moves = vec![CatanMove::EndTurn];
+                self.build_moves(&player, &mut moves);
                 MoveList::Choice(moves)
             }
             GameState::DrawingDevCard => {
-                MoveList::Random((*DEVELOPMENT_CARDS).clone())
+                MoveList::Random(self.dev_deck.iter().filter_map(|&(card, num)| {
+                    if num > 0 {
+                        Some((CatanMove::DrawDevCard(card), num as f64))
+                    } else {
+                        None
+                    }
+                }).collect())
             }
             GameState::ResolvingDevCard(card, step) => {
                 match card {
@@ -547,10 +1394,18 @@ impl Game for Catan {
                         let mut mvs = Vec::new();
                         for (ind, a) in ALL_RESOURCES.iter().enumerate() {
                             for b in ALL_RESOURCES.iter().skip(ind) {
-                                mvs.push(CatanMove::ReceiveYearOfPlenty(*a, *b));
+                                let needed = if a == b { 2 } else { 1 };
+                                if self.get_bank_resource(*a) >= needed && self.get_bank_resource(*b) >= needed {
+                                    mvs.push(CatanMove::ReceiveYearOfPlenty(*a, *b));
+                                }
                             }
                         }
-                        MoveList::Choice(mvs)
+                        if mvs.len() > 0 {
+                            MoveList::Choice(mvs)
+                        } else {
+                            // the bank is empty, so the card is wasted
+                            MoveList::Choice(vec![CatanMove::SetState(GameState::Turn)])
+                        }
                     }
                     DevCard::Monopoly => {
                         MoveList::Choice(ALL_RESOURCES.iter().map(|res| {
@@ -559,6 +1414,11 @@ impl Game for Catan {
                     }
                     DevCard::RoadBuilding => {
                         let (buildable_edges, _) = player.get_buildable_spaces(&self);
+                        if player.roads_left == 0 || buildable_edges.is_empty() {
+                            // nowhere left to put a road
+                            return MoveList::Choice(vec![CatanMove::SetState(GameState::Turn)]);
+                        }
+
                         let next_state = if step == 0 {
                             GameState::ResolvingDevCard(DevCard::RoadBuilding, 1)
                         } else {
@@ -590,21 +1450,33 @@ impl Game for Catan {
             // Setup
             &CatanMove::PlaceSettlement(vertex_id) => {
                 self.check_for_port(vertex_id, self.cur_player);
+                self.check_island_bonus(vertex_id);
+                match self.state {
+                    GameState::SetupSettlements { first_player } => {
+                        state_change = Some(GameState::SetupSettlementRoad { first_player, settlement: vertex_id });
+                    }
+                    GameState::SetupCities { first_player } => {
+                        state_change = Some(GameState::SetupCityRoad { first_player, city: vertex_id });
+                    }
+                    _ => { }
+                }
                 let mut vertex = self.vertices.get_mut(&vertex_id).unwrap();
                 vertex.structure = Some((Structure::Settlement, self.cur_player));
-                if let GameState::SetupSettlements { first_player } = self.state {
-                    state_change = Some(GameState::SetupSettlementRoad { first_player, settlement: vertex_id });
-                }
                 let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
                 player.settlements.insert(vertex_id);
+                player.settlements_left -= 1;
             }
             &CatanMove::PlaceCity(vertex_id) => {
                 self.check_for_port(vertex_id, self.cur_player);
+                self.check_island_bonus(vertex_id);
                 let mut vertex = self.vertices.get_mut(&vertex_id).unwrap();
                 vertex.structure = Some((Structure::City, self.cur_player));
                 if let GameState::SetupCities { first_player } = self.state {
                     state_change = Some(GameState::SetupCityRoad { first_player, city: vertex_id });
                 }
+                let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                player.cities.insert(vertex_id);
+                player.cities_left -= 1;
             }
             &CatanMove::PlaceRoad(edge_id) => {
                 {
@@ -612,20 +1484,37 @@ impl Game for Catan {
                     let mut player = self.players.get_mut(&self.cur_player).unwrap().get_mut();
                     edge.road = Some(self.cur_player);
                     player.roads.insert(edge_id);
+                    player.roads_left -= 1;
 
                     if let GameState::SetupSettlementRoad { first_player, settlement } = self.state {
                         if player.static_data.next_player == first_player {
+                            // in snake order the last player goes again
+                            if self.rules.setup_order == SetupOrder::Repeated {
+                                self.cur_player = first_player;
+                            }
                             state_change = Some(GameState::SetupCities { first_player });
                         } else {
                             self.cur_player = player.static_data.next_player;
                             state_change = Some(GameState::SetupSettlements { first_player });
                         }
                     } else if let GameState::SetupCityRoad { first_player, city } = self.state {
-                        if self.cur_player == first_player {
-                            state_change = Some(GameState::Roll);
-                        } else {
-                            self.cur_player = player.static_data.prev_player;
-                            state_change = Some(GameState::SetupCities { first_player });
+                        match self.rules.setup_order {
+                            SetupOrder::Snake => {
+                                if self.cur_player == first_player {
+                                    state_change = Some(GameState::Roll);
+                                } else {
+                                    self.cur_player = player.static_data.prev_player;
+                                    state_change = Some(GameState::SetupCities { first_player });
+                                }
+                            }
+                            SetupOrder::Repeated => {
+                                self.cur_player = player.static_data.next_player;
+                                if self.cur_player == first_player {
+                                    state_change = Some(GameState::Roll);
+                                } else {
+                                    state_change = Some(GameState::SetupCities { first_player });
+                                }
+                            }
                         }
                     }
                 }
@@ -633,132 +1522,377 @@ impl Game for Catan {
                 match self.state {
                     GameState::SetupCityRoad { .. } | GameState::SetupSettlementRoad { .. } => { }
                     _ => {
-                        state_change = self.check_longest_road(edge_id).or(state_change);
+                        self.update_longest_road();
                     }
                 }
             }
             &CatanMove::Roll(roll) => {
-                if roll == 7 {
-                    state_change = Some(GameState::MovingRobber)
-                } else {
-
-                    for (hex_pos, hex) in self.hexes.iter() {
-                        if hex.static_data.roll == roll && *hex_pos != self.robber_pos {
-                            if let HexType::Land(resource) = hex.static_data.typ {
-                                for vertex_id in hex.static_data.vertices.iter() {
-                                    let vertex = self.vertices.get(vertex_id).unwrap();
-                                    if let Some((structure, player_id)) = vertex.structure {
-                                        match structure {
-                                            Structure::Settlement => {
-                                                let mut player = self.players.get(&player_id).unwrap().borrow_mut();
-                                                player.give_resource(resource, 1);
-                                            },
-                                            Structure::City => {
-                                                let mut player = self.players.get(&player_id).unwrap().borrow_mut();
-                                                player.give_resource(resource, 2);
-                                            },
-                                            _ => { }
-                                        }
-                                    }
-                                }
+                self.has_rolled = true;
+                state_change = Some(self.resolve_roll(roll));
+            }
+            &CatanMove::RollDice { red, yellow, event } => {
+                self.has_rolled = true;
+                self.pending_roll = Some(red + yellow);
+
+                let roller = self.cur_player;
+                match event {
+                    EventDie::Ship => {
+                        self.barbarian_pos += 1;
+                        if self.barbarian_pos >= self.rules.barbarian_distance {
+                            self.barbarian_attack();
+                        }
+                    }
+                    EventDie::Gate(track) => {
+                        // everyone whose improvement in that track covers the red die draws a card
+                        let mut player_id = roller;
+                        loop {
+                            let player = self.players.get(&player_id).unwrap().borrow();
+                            let level = player.improvements[track as usize];
+                            if level > 0 && red <= level + 1 {
+                                self.progress_draws.push((player_id, track));
+                            }
+                            player_id = player.static_data.next_player;
+                            if player_id == roller {
+                                break;
                             }
                         }
                     }
+                }
 
-                    state_change = Some(GameState::Turn);
+                state_change = Some(self.continue_roll(roller));
+            }
+            &CatanMove::Discard(ref cards) => {
+                let next_player = {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    for &(res, num) in cards {
+                        pay_bank(&mut player, &mut self.bank, res, num);
+                    }
+                    player.static_data.next_player
+                };
+
+                if let GameState::Discarding { roller } = self.state {
+                    let next_discarder = if next_player == roller {
+                        None
+                    } else {
+                        self.next_discarding_player(roller, next_player)
+                    };
+
+                    if let Some(player_id) = next_discarder {
+                        self.cur_player = player_id;
+                    } else {
+                        // everyone has discarded; the robber goes back to the player who rolled
+                        self.cur_player = roller;
+                        state_change = Some(self.robber_state());
+                    }
                 }
             }
-            &CatanMove::MoveRobber(hex_pos, player_id) => {
+            &CatanMove::ChooseGold(ref cards) => {
+                {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    for &(res, num) in cards {
+                        take_from_bank(&mut player, &mut self.bank, res, num);
+                    }
+                }
+
+                self.gold_owed.remove(0);
+                if let GameState::ChoosingGold { roller } = self.state {
+                    if let Some(&(player_id, _)) = self.gold_owed.first() {
+                        self.cur_player = player_id;
+                    } else {
+                        self.cur_player = roller;
+                        state_change = Some(GameState::Turn);
+                    }
+                }
+            }
+            &CatanMove::MovePirate(hex_pos, victim) => {
+                self.pirate_pos = Some(hex_pos);
+
+                state_change = Some(match victim {
+                    Some(player_id) => GameState::StealingCards(player_id),
+                    None => self.after_robber_state(),
+                });
+            }
+            &CatanMove::MoveRobber(hex_pos, victim) => {
                 self.robber_pos = hex_pos;
 
-                state_change = Some(GameState::StealingCards(player_id));
+                state_change = Some(match victim {
+                    Some(player_id) => GameState::StealingCards(player_id),
+                    None => self.after_robber_state(),
+                });
             }
             &CatanMove::Steal(resource, from_id) => {
                 let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
                 let mut from = self.players.get(&from_id).unwrap().borrow_mut();
 
-                if from.get_resource(resource) == 0 {
-                    println!("!!!");
-                } else {
+                // a validated move never steals a card the victim doesn't have
+                if from.get_resource(resource) > 0 {
                     from.consume_resource(resource, 1);
+                    player.give_resource(resource, 1);
                 }
 
-                player.give_resource(resource, 1);
-
-                state_change = Some(GameState::Turn);
+                state_change = Some(self.after_robber_state());
             }
             &CatanMove::EndTurn => {
-                let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                for card in self.new_dev_cards.drain(0..) {
-                    player.dev_cards.push(card);
+                let next_player = {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    for card in self.new_dev_cards.drain(0..) {
+                        player.dev_cards.push(card);
+                    }
+                    player.static_data.next_player
+                };
+                self.trades_offered = 0;
+                self.dev_card_played = false;
+                self.has_rolled = false;
+
+                // everyone else gets a chance to build before the next turn starts
+                let turn_player = self.special_building.unwrap_or(self.cur_player);
+                if self.rules.special_building_phase && next_player != turn_player {
+                    self.special_building = Some(turn_player);
+                    self.cur_player = next_player;
+                    state_change = Some(GameState::SpecialBuilding);
+                } else {
+                    self.special_building = None;
+                    self.cur_player = self.players.get(&turn_player).unwrap().borrow().static_data.next_player;
+
+                    let player = self.players.get(&self.cur_player).unwrap().borrow();
+                    if player.dev_cards.contains(&DevCard::Soldier) {
+                        state_change = Some(GameState::PreRoll);
+                    } else {
+                        state_change = Some(GameState::Roll);
+                    }
                 }
-                self.cur_player = player.static_data.next_player;
-                state_change = Some(GameState::Roll);
             }
 
             // Building / Upgrading
             &CatanMove::BuildRoad(edge) => {
                 {
                     let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                    player.consume_resource(Resource::Wood, 1);
-                    player.consume_resource(Resource::Brick, 1);
+                    pay_cost(&mut player, &mut self.bank, &ROAD_COST);
                     self.edges.get_mut(&edge).unwrap().road = Some(self.cur_player);
                     player.roads.insert(edge);
+                    player.roads_left -= 1;
+                }
+
+                self.update_longest_road();
+            }
+            &CatanMove::BuildShip(edge) => {
+                {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    pay_cost(&mut player, &mut self.bank, &SHIP_COST);
+                    self.edges.get_mut(&edge).unwrap().ship = Some(self.cur_player);
+                    player.ships.insert(edge);
+                    player.ships_left -= 1;
                 }
 
-                state_change = self.check_longest_road(edge);
+                self.update_longest_road();
             }
             &CatanMove::BuildSettlement(vertex) => {
                 self.check_for_port(vertex, self.cur_player);
-                let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                player.consume_resource(Resource::Wood, 1);
-                player.consume_resource(Resource::Brick, 1);
-                player.consume_resource(Resource::Sheep, 1);
-                player.consume_resource(Resource::Wheat, 1);
-                self.vertices.get_mut(&vertex).unwrap().structure = Some((Structure::Settlement, self.cur_player));
-                player.settlements.insert(vertex);
+                self.check_island_bonus(vertex);
+                {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    pay_cost(&mut player, &mut self.bank, &SETTLEMENT_COST);
+                    self.vertices.get_mut(&vertex).unwrap().structure = Some((Structure::Settlement, self.cur_player));
+                    player.settlements.insert(vertex);
+                    player.settlements_left -= 1;
 
-                player.victory_points += 1;
-                if player.victory_points >= 10 {
-                    state_change = Some(GameState::GameOver);
+                    player.victory_points += 1;
                 }
+
+                // the new settlement may have cut someone else's road in two
+                self.update_longest_road();
             }
             &CatanMove::BuildCity(vertex) => {
                 let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                player.consume_resource(Resource::Wheat, 2);
-                player.consume_resource(Resource::Rock, 3);
+                pay_cost(&mut player, &mut self.bank, &CITY_COST);
                 self.vertices.get_mut(&vertex).unwrap().structure = Some((Structure::City, self.cur_player));
                 player.settlements.remove(&vertex);
+                player.cities.insert(vertex);
+                player.settlements_left += 1;
+                player.cities_left -= 1;
 
                 player.victory_points += 1;
-                if player.victory_points >= 10 {
-                    state_change = Some(GameState::GameOver);
+            }
+
+            &CatanMove::BuildKnight(vertex) => {
+                {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    pay_cost(&mut player, &mut self.bank, &KNIGHT_COST);
+                    self.vertices.get_mut(&vertex).unwrap().structure = Some((Structure::Knight { level: 1, active: false }, self.cur_player));
+                    player.knights_left[0] -= 1;
+                }
+
+                // knights block other players' roads just like settlements do
+                self.update_longest_road();
+            }
+            &CatanMove::ActivateKnight(vertex) => {
+                let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                pay_cost(&mut player, &mut self.bank, &ACTIVATE_COST);
+                let vertex = self.vertices.get_mut(&vertex).unwrap();
+                if let Some((Structure::Knight { level, .. }, owner)) = vertex.structure {
+                    vertex.structure = Some((Structure::Knight { level, active: true }, owner));
+                }
+            }
+            &CatanMove::PromoteKnight(vertex) => {
+                let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                pay_cost(&mut player, &mut self.bank, &KNIGHT_COST);
+                let vertex = self.vertices.get_mut(&vertex).unwrap();
+                if let Some((Structure::Knight { level, active }, owner)) = vertex.structure {
+                    vertex.structure = Some((Structure::Knight { level: level + 1, active }, owner));
+                    player.knights_left[level as usize - 1] += 1;
+                    player.knights_left[level as usize] -= 1;
+                }
+            }
+            &CatanMove::ChaseRobber(vertex) => {
+                let vertex = self.vertices.get_mut(&vertex).unwrap();
+                if let Some((Structure::Knight { level, .. }, owner)) = vertex.structure {
+                    vertex.structure = Some((Structure::Knight { level, active: false }, owner));
+                }
+                state_change = Some(GameState::MovingRobber);
+            }
+            &CatanMove::ImproveCity(track) => {
+                let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                let level = player.improvements[track as usize] + 1;
+                pay_bank(&mut player, &mut self.bank, track.commodity(), level);
+                player.improvements[track as usize] = level;
+
+                // the first to level 4 gets the metropolis, and only level 5 can take it away
+                if level >= METROPOLIS_LEVEL {
+                    match self.metropolises[track as usize] {
+                        None => {
+                            player.victory_points += 2;
+                            self.metropolises[track as usize] = Some(self.cur_player);
+                        }
+                        Some(owner) if owner != self.cur_player => {
+                            let mut other_player = self.players.get(&owner).unwrap().borrow_mut();
+                            if other_player.improvements[track as usize] < level {
+                                other_player.victory_points -= 2;
+                                player.victory_points += 2;
+                                self.metropolises[track as usize] = Some(self.cur_player);
+                            }
+                        }
+                        _ => { }
+                    }
+                }
+            }
+            &CatanMove::LoseCity(vertex) => {
+                {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    self.vertices.get_mut(&vertex).unwrap().structure = Some((Structure::Settlement, self.cur_player));
+                    player.settlements.insert(vertex);
+                    player.cities.remove(&vertex);
+                    player.settlements_left = player.settlements_left.saturating_sub(1);
+                    player.cities_left += 1;
+                    player.victory_points -= 1;
+                }
+
+                self.pillaged.remove(0);
+                if let GameState::PillagingCity { roller } = self.state {
+                    state_change = Some(self.continue_roll(roller));
                 }
             }
 
+            // Progress Cards
+            &CatanMove::DrawProgressCard(card) => {
+                for entry in self.progress_decks.iter_mut().flat_map(|deck| deck.iter_mut()) {
+                    if entry.0 == card {
+                        entry.1 -= 1;
+                    }
+                }
+
+                {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    if card.is_victory_point() {
+                        // these are shown right away, unlike victory point development cards
+                        player.victory_points += 1;
+                    } else {
+                        player.progress_cards.push(card);
+                    }
+                }
+
+                self.progress_draws.remove(0);
+                if let GameState::DrawingProgressCard { roller, .. } = self.state {
+                    state_change = Some(self.continue_roll(roller));
+                }
+            }
+            &CatanMove::PlayProgressCard(card) => {
+                {
+                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                    player.progress_cards.remove_item(&card);
+                }
+                match card {
+                    ProgressCard::Irrigation => self.harvest(Resource::Wheat),
+                    ProgressCard::Mining => self.harvest(Resource::Rock),
+                    ProgressCard::RoadBuilding => {
+                        state_change = Some(GameState::ResolvingDevCard(DevCard::RoadBuilding, 0));
+                    }
+                    ProgressCard::ResourceMonopoly | ProgressCard::TradeMonopoly => {
+                        state_change = Some(GameState::ResolvingProgressCard(card));
+                    }
+                    ProgressCard::Bishop => {
+                        // only robs one player, instead of everyone next to the robber
+                        state_change = Some(GameState::MovingRobber);
+                    }
+                    ProgressCard::Warlord => {
+                        for vertex in self.vertices.values_mut() {
+                            if let Some((Structure::Knight { level, .. }, owner)) = vertex.structure {
+                                if owner == self.cur_player {
+                                    vertex.structure = Some((Structure::Knight { level, active: true }, owner));
+                                }
+                            }
+                        }
+                    }
+                    // victory point cards are played as soon as they're drawn
+                    ProgressCard::Printer | ProgressCard::Constitution => { unreachable!() }
+                }
+            }
+            &CatanMove::ReceiveProgressMonopoly(resource) => {
+                // 2 of a resource, or 1 of a commodity, from each other player
+                let limit = if ALL_COMMODITIES.contains(&resource) { 1 } else { 2 };
+                let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
+                for (player_id, player_cell) in self.players.iter() {
+                    if *player_id != self.cur_player {
+                        let mut other_player = player_cell.borrow_mut();
+                        let count = other_player.get_resource(resource).min(limit);
+                        if count > 0 {
+                            other_player.consume_resource(resource, count);
+                            player.give_resource(resource, count);
+                        }
+                    }
+                }
+                state_change = Some(GameState::Turn);
+            }
+
             // Development Cards
             &CatanMove::BuyDevCard => {
                 let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                player.consume_resource(Resource::Sheep, 1);
-                player.consume_resource(Resource::Wheat, 1);
-                player.consume_resource(Resource::Rock, 1);
+                pay_cost(&mut player, &mut self.bank, &DEV_CARD_COST);
                 state_change = Some(GameState::DrawingDevCard);
             }
             &CatanMove::DrawDevCard(card) => {
-                if card == DevCard::VictoryPoint {
-                    let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                    player.victory_points += 1;
-                    if player.victory_points >= 10 {
-                        state_change = Some(GameState::GameOver);
+                for entry in self.dev_deck.iter_mut() {
+                    if entry.0 == card {
+                        entry.1 -= 1;
                     }
+                }
+
+                if card == DevCard::VictoryPoint {
+                    // victory point cards stay hidden in the hand, but count right away
+                    self.players.get(&self.cur_player).unwrap().borrow_mut().dev_cards.push(card);
                 } else {
                     self.new_dev_cards.push(card);
                 }
-                state_change = Some(GameState::Turn);
+
+                if self.special_building.is_some() {
+                    state_change = Some(GameState::SpecialBuilding);
+                } else {
+                    state_change = Some(GameState::Turn);
+                }
             }
             &CatanMove::PlayDevCard(card) => {
                 let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
                 player.dev_cards.remove_item(&card);
+                self.dev_card_played = true;
                 match card {
                     DevCard::Soldier => {
                         state_change = Some(GameState::MovingRobber);
@@ -799,53 +1933,167 @@ impl Game for Catan {
             }
             &CatanMove::ReceiveYearOfPlenty(res1, res2) => {
                 let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                player.give_resource(res1, 1);
-                player.give_resource(res2, 1);
+                take_from_bank(&mut player, &mut self.bank, res1, 1);
+                take_from_bank(&mut player, &mut self.bank, res2, 1);
                 state_change = Some(GameState::Turn);
             }
 
             // Trade
-            &CatanMove::Trade {to, ref give, ref take} => {
-                let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                let mut other_player = self.players.get(&to).unwrap().borrow_mut();
-                for &(res, num) in give {
-                    player.consume_resource(res, num);
-                    other_player.give_resource(res, num);
+            &CatanMove::OfferTrade {to, ref give, ref take} => {
+                self.pending_trade = Some(TradeOffer {
+                    from: self.cur_player,
+                    to,
+                    give: give.clone(),
+                    take: take.clone(),
+                });
+                self.trades_offered += 1;
+                state_change = Some(GameState::TradeOffered { proposer: self.cur_player });
+                self.cur_player = to;
+            }
+            &CatanMove::AcceptTrade => {
+                let offer = self.pending_trade.take().expect("No trade has been offered");
+                self.exchange(offer.from, offer.to, &offer.give, &offer.take);
+                self.cur_player = offer.from;
+                state_change = Some(GameState::Turn);
+            }
+            &CatanMove::RejectTrade => {
+                let offer = self.pending_trade.take().expect("No trade has been offered");
+                self.cur_player = offer.from;
+                state_change = Some(GameState::Turn);
+            }
+            &CatanMove::CounterTrade {ref give, ref take} => {
+                state_change = Some(GameState::TradeCountered { responder: self.cur_player });
+                if let Some(ref mut offer) = self.pending_trade {
+                    offer.give = give.clone();
+                    offer.take = take.clone();
+                    self.cur_player = offer.from;
                 }
-                for &(res, num) in take {
-                    player.give_resource(res, num);
-                    other_player.consume_resource(res, num);
+            }
+            &CatanMove::Trade {to, ref give, ref take} => {
+                self.exchange(self.cur_player, to, give, take);
+
+                if let GameState::TradeCountered { .. } = self.state {
+                    self.pending_trade = None;
+                    state_change = Some(GameState::Turn);
                 }
             }
 
             &CatanMove::Convert4x {from, to}  => {
                 let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                player.consume_resource(from, 4);
-                player.give_resource(to, 1);
+                pay_bank(&mut player, &mut self.bank, from, 4);
+                take_from_bank(&mut player, &mut self.bank, to, 1);
             }
             &CatanMove::Convert3x {from, to}  => {
                 let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                player.consume_resource(from, 3);
-                player.give_resource(to, 1);
+                pay_bank(&mut player, &mut self.bank, from, 3);
+                take_from_bank(&mut player, &mut self.bank, to, 1);
             }
             &CatanMove::Convert2x {from, to}  => {
                 let mut player = self.players.get(&self.cur_player).unwrap().borrow_mut();
-                player.consume_resource(from, 2);
-                player.give_resource(to, 1);
+                pay_bank(&mut player, &mut self.bank, from, 2);
+                take_from_bank(&mut player, &mut self.bank, to, 1);
             }
         }
         if let Some(state) = state_change {
             self.state = state;
         }
+
+        if self.state != GameState::GameOver {
+            let turn_player = self.turn_player();
+            let points = self.players.get(&turn_player).unwrap().borrow().total_victory_points();
+            if points >= self.rules.victory_points {
+                self.winner = Some(turn_player);
+                self.state = GameState::GameOver;
+            }
+        }
     }
     fn get_cur_player(&self) -> Self::Player {
         self.cur_player
     }
     fn get_winner(&self) -> Option<Self::Player> {
-        if self.state == GameState::GameOver {
-            Some(self.cur_player)
-        } else {
-            None
+        self.winner
+    }
+    fn get_rewards(&self) -> Vec<(Self::Player, f64)> {
+        if let Some(winner) = self.winner {
+            return vec![(winner, 1.0)];
+        }
+
+        // a game cut short is shared out by victory points
+        let points: Vec<(PlayerID, u8)> = self.players.iter()
+            .map(|(id, player)| (*id, player.borrow().total_victory_points()))
+            .collect();
+        let total: u32 = points.iter().map(|&(_, vp)| vp as u32).sum();
+        if total == 0 {
+            return Vec::new();
+        }
+        points.into_iter().map(|(id, vp)| (id, vp as f64 / total as f64)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ai::Game;
+    use catan::*;
+    use catan::testing::*;
+
+    #[test]
+    fn drawing_a_victory_point_off_turn_does_not_win() {
+        let mut catan = game_with(CatanRules::cities_and_knights());
+        let mut rng = seeded_rng(5);
+        while catan.state != GameState::Turn {
+            let mv = random_move(&catan, &mut rng).unwrap();
+            catan.make_move(&mv);
+        }
+        let roller = catan.cur_player;
+        let drawer = catan.players[&roller].borrow().static_data.next_player;
+        {
+            let mut player = catan.players[&drawer].borrow_mut();
+            let short = catan.rules.victory_points - 1 - player.total_victory_points();
+            player.victory_points += short;
         }
+        catan.progress_draws = vec![(drawer, Improvement::Science)];
+        catan.cur_player = drawer;
+        catan.state = GameState::DrawingProgressCard { roller, track: Improvement::Science };
+
+        catan.make_move(&CatanMove::DrawProgressCard(ProgressCard::Printer));
+        assert_eq!(catan.players[&drawer].borrow().total_victory_points(), catan.rules.victory_points);
+        assert_eq!(catan.get_winner(), None);
+        assert_eq!(catan.state, GameState::Turn);
+
+        // they still haven't won while they wait behind the roller to draw again
+        catan.progress_draws = vec![(roller, Improvement::Science), (drawer, Improvement::Science)];
+        catan.state = GameState::DrawingProgressCard { roller, track: Improvement::Science };
+
+        catan.make_move(&CatanMove::DrawProgressCard(ProgressCard::Irrigation));
+        assert_eq!(catan.cur_player, drawer);
+        assert_eq!(catan.get_winner(), None);
+        assert_eq!(catan.state, GameState::DrawingProgressCard { roller, track: Improvement::Science });
+
+        catan.make_move(&CatanMove::DrawProgressCard(ProgressCard::Mining));
+        assert_eq!(catan.get_winner(), None);
+        assert_eq!(catan.state, GameState::Turn);
+        assert_eq!(catan.cur_player, roller);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn islands_are_part_of_the_position() {
+        let catan = game_with(CatanRules::seafarers());
+        let player_id = catan.cur_player;
+
+        let mut home = catan.clone();
+        home.home_islands.insert(1);
+        assert!(home.get_hash() != catan.get_hash());
+
+        let mut settled = catan.clone();
+        settled.players[&player_id].borrow_mut().islands.insert(1);
+        assert!(settled.get_hash() != catan.get_hash());
+
+        // the order they were added in doesn't matter
+        let (mut a, mut b) = (catan.clone(), catan.clone());
+        for island in 0..20 {
+            a.home_islands.insert(island);
+            b.home_islands.insert(19 - island);
+        }
+        assert_eq!(a.get_hash(), b.get_hash());
+    }
+}
@@ -0,0 +1,98 @@
+use catan::*;
+
+// players can have at most this many knights of each level
+pub const KNIGHTS_PER_LEVEL: u8 = 2;
+
+// a level 4 improvement earns a metropolis, and only a level 5 one can take it away
+pub const METROPOLIS_LEVEL: u8 = 4;
+pub const MAX_IMPROVEMENT_LEVEL: u8 = 5;
+
+// The three city improvement tracks, each paid for with one kind of commodity
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Improvement {
+    Trade,    // yellow; cloth
+    Politics, // blue; coin
+    Science,  // green; paper
+}
+pub const ALL_IMPROVEMENTS: [Improvement; 3] =
+    [ Improvement::Trade, Improvement::Politics, Improvement::Science ];
+
+impl Improvement {
+    pub fn commodity(&self) -> Resource {
+        match *self {
+            Improvement::Trade    => Resource::Cloth,
+            Improvement::Politics => Resource::Coin,
+            Improvement::Science  => Resource::Paper,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum EventDie {
+    Ship, // the barbarians move one step closer
+    Gate(Improvement), // players with that improvement may draw a progress card
+}
+
+// three of the six sides show the barbarian ship
+pub const EVENT_DIE: [(EventDie, f64); 4] = [
+    (EventDie::Ship, 3.0 / 6.0),
+    (EventDie::Gate(Improvement::Trade), 1.0 / 6.0),
+    (EventDie::Gate(Improvement::Politics), 1.0 / 6.0),
+    (EventDie::Gate(Improvement::Science), 1.0 / 6.0),
+];
+
+// Only some of the progress cards are implemented; the decks are made up of those
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ProgressCard {
+    // Science
+    Irrigation, // 2 wheat for each wheat hex next to the player's buildings
+    Mining,     // 2 rock for each rock hex next to the player's buildings
+    RoadBuilding,
+    Printer,    // victory point
+
+    // Trade
+    ResourceMonopoly, // up to 2 of a resource from every other player
+    TradeMonopoly,    // 1 of a commodity from every other player
+
+    // Politics
+    Bishop,       // move the robber
+    Warlord,      // activate all of the player's knights for free
+    Constitution, // victory point
+}
+
+impl ProgressCard {
+    pub fn is_victory_point(&self) -> bool {
+        *self == ProgressCard::Printer || *self == ProgressCard::Constitution
+    }
+}
+
+// The cards in each deck, indexed by `Improvement as usize`
+pub fn progress_decks() -> [Vec<(ProgressCard, u8)>; 3] {
+    [
+        vec![
+            (ProgressCard::ResourceMonopoly, 4),
+            (ProgressCard::TradeMonopoly,    2),
+        ],
+        vec![
+            (ProgressCard::Bishop,       2),
+            (ProgressCard::Warlord,      2),
+            (ProgressCard::Constitution, 1),
+        ],
+        vec![
+            (ProgressCard::Irrigation,   2),
+            (ProgressCard::Mining,       2),
+            (ProgressCard::RoadBuilding, 2),
+            (ProgressCard::Printer,      1),
+        ],
+    ]
+}
+
+// Cities on these hexes produce one resource and one commodity instead of two resources
+pub fn city_commodity(resource: Resource) -> Option<Resource> {
+    match resource {
+        Resource::Rock  => Some(Resource::Coin),
+        Resource::Sheep => Some(Resource::Cloth),
+        Resource::Wood  => Some(Resource::Paper),
+        _ => None,
+    }
+}
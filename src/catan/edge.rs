@@ -19,6 +19,7 @@ pub struct Edge {
 	pub static_data: Arc<EdgeStatic>,
 
     pub road: Option<PlayerID>,
+    pub ship: Option<PlayerID>, // Seafarers
 }
 
 const EDGE_ANGLES: [f64; 3] = [-PI / 3.0, 0.0, PI / 3.0];
@@ -26,12 +27,17 @@ impl Edge {
     pub fn new(static_data: EdgeStatic) -> Self {
     	Edge {
         	static_data: Arc::new(static_data),
-            road: None
+            road: None,
+            ship: None,
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.road.is_none() && self.ship.is_none()
+    }
+
     pub fn draw(&self, ctx: &Context, catan: &Catan, id: EdgeID) {
-        if let Some(player) = self.road {
+        if let Some(player) = self.road.or(self.ship) {
             let pos = self.static_data.hex_position;
             let side = self.static_data.hex_side;
 
@@ -45,7 +51,21 @@ impl Edge {
 
             let player_color = catan.get_player_color(player);
             ctx.set_source_rgb(player_color[0], player_color[1], player_color[2]);
-            ctx.rectangle(-HEX_SCALE / 2.0, -3.0, HEX_SCALE, 6.0);
+            if self.ship.is_some() {
+                // hull
+                ctx.move_to(-HEX_SCALE / 4.0, 0.0);
+                ctx.line_to(HEX_SCALE / 4.0, 0.0);
+                ctx.line_to(HEX_SCALE / 6.0, 6.0);
+                ctx.line_to(-HEX_SCALE / 6.0, 6.0);
+                ctx.close_path();
+                // sail
+                ctx.move_to(0.0, 0.0);
+                ctx.line_to(0.0, -HEX_SCALE / 4.0);
+                ctx.line_to(HEX_SCALE / 8.0, -2.0);
+                ctx.close_path();
+            } else {
+                ctx.rectangle(-HEX_SCALE / 2.0, -3.0, HEX_SCALE, 6.0);
+            }
             ctx.fill_preserve();
             ctx.set_source_rgb(0.0, 0.0, 0.0);
             ctx.stroke();
@@ -61,5 +81,6 @@ impl Edge {
 impl Hash for Edge {
     fn hash<H>(&self, state: &mut H) where H: Hasher {
         self.road.hash(state);
+        self.ship.hash(state);
     }
 }
\ No newline at end of file
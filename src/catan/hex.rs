@@ -16,6 +16,7 @@ pub enum HexType {
     Port3to1(u8),
     Desert,
     Land(Resource),
+    Gold, // Seafarers; produces whichever resource the player chooses
 }
 
 pub struct HexStatic {
@@ -70,6 +71,7 @@ impl Hex {
             HexType::Water | HexType::Port2to1(_, _) | HexType::Port3to1(_) 
                                 => color(0x2D4CE5),
             HexType::Desert     => color(0xFFD000),
+            HexType::Gold       => color(0xD4AF37),
             HexType::Land(resource) => match resource {
                 Resource::Wheat => color(0xFFE032),
                 Resource::Sheep => color(0xB7E045),
@@ -83,7 +85,7 @@ impl Hex {
         ctx.set_source_rgb(col[0], col[1], col[2]);
 
         match self.static_data.typ {
-            HexType::Desert | HexType::Land(_) => {
+            HexType::Desert | HexType::Land(_) | HexType::Gold => {
                 ctx.fill_preserve();
                 let border_col = color(0xFFEBB2);
                 ctx.line_to(HEX_POINTS[0].0, HEX_POINTS[0].1);
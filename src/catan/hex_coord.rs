@@ -22,6 +22,14 @@ impl HexCoord {
         HexCoord {q, r}
     }
 
+    pub fn q(&self) -> i16 {
+        self.q
+    }
+
+    pub fn r(&self) -> i16 {
+        self.r
+    }
+
     pub fn neighbors(&self) -> [HexCoord; 6] {
         [
             self.neighbor(0), self.neighbor(1), self.neighbor(2),
@@ -0,0 +1,529 @@
+// Checking moves that come from outside the AI, like a human or network player, before
+// they reach `make_move`, which trusts whatever it's given. A move is legal exactly when
+// it's one of the available moves; the rest of this works out why one isn't.
+
+use std::fmt::{ self, Display, Formatter };
+
+use ai::{ Game, MoveList };
+use catan::*;
+use catan::catan::{ has_cards, MAX_TRADE_OFFERS_PER_TURN };
+use catan::catan::{ ROAD_COST, SHIP_COST, SETTLEMENT_COST, CITY_COST, DEV_CARD_COST, KNIGHT_COST, ACTIVATE_COST };
+use catan::player::Player;
+use catan::vertex::Structure;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum IllegalMove {
+    GameOver,
+    WrongPhase(GameState), // the move can't be made at this point in the turn
+    NotInRules, // the move belongs to an expansion that isn't being played
+
+    UnknownVertex(VertexID),
+    UnknownEdge(EdgeID),
+    UnknownHex(HexCoord),
+    UnknownPlayer(PlayerID),
+
+    InsufficientResources,
+    NoPiecesLeft,
+    VertexOccupied(VertexID),
+    EdgeOccupied(EdgeID),
+    TooCloseToSettlement(VertexID), // the distance rule
+    NotConnected, // not next to the player's roads, ships or buildings
+    NotOwned(VertexID), // the player has no such building or knight there
+
+    CardNotHeld,
+    DevCardAlreadyPlayed,
+    DeckEmpty,
+    BankEmpty,
+    NoPort,
+    InvalidRobberPosition(HexCoord),
+    TooManyTradeOffers,
+    WrongNumberOfCards { expected: u8, got: u8 },
+    ImpossibleOutcome, // a roll, draw or steal that couldn't have happened
+
+    NotAvailable, // anything else that isn't one of the available moves
+}
+
+impl Display for IllegalMove {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            IllegalMove::GameOver => write!(f, "the game is over"),
+            IllegalMove::WrongPhase(state) => write!(f, "that can't be done while in {:?}", state),
+            IllegalMove::NotInRules => write!(f, "that isn't part of the rules being played"),
+            IllegalMove::UnknownVertex(id) => write!(f, "there is no vertex {}", id),
+            IllegalMove::UnknownEdge(id) => write!(f, "there is no edge {}", id),
+            IllegalMove::UnknownHex(pos) => write!(f, "there is no hex at {:?}", pos),
+            IllegalMove::UnknownPlayer(id) => write!(f, "there is no player {}", id),
+            IllegalMove::InsufficientResources => write!(f, "not enough resources"),
+            IllegalMove::NoPiecesLeft => write!(f, "no pieces of that kind are left"),
+            IllegalMove::VertexOccupied(id) => write!(f, "vertex {} is already taken", id),
+            IllegalMove::EdgeOccupied(id) => write!(f, "edge {} is already taken", id),
+            IllegalMove::TooCloseToSettlement(id) => write!(f, "vertex {} is too close to another settlement", id),
+            IllegalMove::NotConnected => write!(f, "that isn't connected to anything of the player's"),
+            IllegalMove::NotOwned(id) => write!(f, "the player has nothing at vertex {} to do that with", id),
+            IllegalMove::CardNotHeld => write!(f, "the player doesn't have a card they can play like that"),
+            IllegalMove::DevCardAlreadyPlayed => write!(f, "a development card has already been played this turn"),
+            IllegalMove::DeckEmpty => write!(f, "there are no cards left to draw"),
+            IllegalMove::BankEmpty => write!(f, "the bank doesn't have those cards"),
+            IllegalMove::NoPort => write!(f, "the player has no harbor for that trade"),
+            IllegalMove::InvalidRobberPosition(pos) => write!(f, "it can't be moved to {:?}", pos),
+            IllegalMove::TooManyTradeOffers => write!(f, "no more trades can be offered this turn"),
+            IllegalMove::WrongNumberOfCards { expected, got } => write!(f, "expected {} cards, not {}", expected, got),
+            IllegalMove::ImpossibleOutcome => write!(f, "that couldn't have happened"),
+            IllegalMove::NotAvailable => write!(f, "that move isn't available"),
+        }
+    }
+}
+
+fn num_cards(cards: &[(Resource, u8)]) -> u8 {
+    cards.iter().map(|&(_, num)| num).sum()
+}
+
+fn pay(player: &Player, cost: &[(Resource, u8)]) -> Result<(), IllegalMove> {
+    if has_cards(player, cost) {
+        Ok(())
+    } else {
+        Err(IllegalMove::InsufficientResources)
+    }
+}
+
+impl Catan {
+    pub fn validate_move(&self, mv: &CatanMove) -> Result<(), IllegalMove> {
+        let available = match self.available_moves() {
+            MoveList::Random(moves) => moves.iter().any(|&(ref other, _)| other == mv),
+            MoveList::Choice(moves) => moves.contains(mv),
+        };
+        if available {
+            return Ok(());
+        }
+
+        if self.state == GameState::GameOver {
+            return Err(IllegalMove::GameOver);
+        }
+        self.check_ids(mv)?;
+        if !self.in_rules(mv) {
+            return Err(IllegalMove::NotInRules);
+        }
+        if !self.allowed_in_state(mv) {
+            return Err(IllegalMove::WrongPhase(self.state));
+        }
+        self.diagnose(mv)?;
+        Err(IllegalMove::NotAvailable)
+    }
+
+    pub fn try_make_move(&mut self, mv: &CatanMove) -> Result<(), IllegalMove> {
+        self.validate_move(mv)?;
+        self.make_move(mv);
+        Ok(())
+    }
+
+    fn check_vertex(&self, vertex_id: VertexID) -> Result<(), IllegalMove> {
+        if self.vertices.contains_key(&vertex_id) { Ok(()) } else { Err(IllegalMove::UnknownVertex(vertex_id)) }
+    }
+
+    fn check_edge(&self, edge_id: EdgeID) -> Result<(), IllegalMove> {
+        if self.edges.contains_key(&edge_id) { Ok(()) } else { Err(IllegalMove::UnknownEdge(edge_id)) }
+    }
+
+    fn check_hex(&self, pos: HexCoord) -> Result<(), IllegalMove> {
+        if self.hexes.contains_key(&pos) { Ok(()) } else { Err(IllegalMove::UnknownHex(pos)) }
+    }
+
+    fn check_player(&self, player_id: PlayerID) -> Result<(), IllegalMove> {
+        if self.players.contains_key(&player_id) { Ok(()) } else { Err(IllegalMove::UnknownPlayer(player_id)) }
+    }
+
+    // Everything the move refers to has to exist before anything else can be looked at
+    fn check_ids(&self, mv: &CatanMove) -> Result<(), IllegalMove> {
+        match mv {
+            &CatanMove::Series(ref moves) => {
+                for mv in moves.iter() {
+                    self.check_ids(mv)?;
+                }
+                Ok(())
+            }
+            &CatanMove::PlaceSettlement(vertex) | &CatanMove::PlaceCity(vertex) |
+            &CatanMove::BuildSettlement(vertex) | &CatanMove::BuildCity(vertex) |
+            &CatanMove::BuildKnight(vertex) | &CatanMove::ActivateKnight(vertex) |
+            &CatanMove::PromoteKnight(vertex) | &CatanMove::ChaseRobber(vertex) |
+            &CatanMove::LoseCity(vertex) => self.check_vertex(vertex),
+            &CatanMove::PlaceRoad(edge) | &CatanMove::BuildRoad(edge) | &CatanMove::BuildShip(edge) => self.check_edge(edge),
+            &CatanMove::MoveRobber(pos, victim) | &CatanMove::MovePirate(pos, victim) => {
+                self.check_hex(pos)?;
+                match victim {
+                    Some(player_id) => self.check_player(player_id),
+                    None => Ok(()),
+                }
+            }
+            &CatanMove::Steal(_, player_id) |
+            &CatanMove::OfferTrade { to: player_id, .. } |
+            &CatanMove::Trade { to: player_id, .. } => self.check_player(player_id),
+            _ => Ok(()),
+        }
+    }
+
+    fn in_rules(&self, mv: &CatanMove) -> bool {
+        match mv {
+            &CatanMove::BuildShip(_) | &CatanMove::MovePirate(..) | &CatanMove::ChooseGold(_) => self.rules.seafarers,
+            &CatanMove::RollDice { .. } | &CatanMove::LoseCity(_) |
+            &CatanMove::BuildKnight(_) | &CatanMove::ActivateKnight(_) |
+            &CatanMove::PromoteKnight(_) | &CatanMove::ChaseRobber(_) |
+            &CatanMove::ImproveCity(_) | &CatanMove::DrawProgressCard(_) |
+            &CatanMove::PlayProgressCard(_) | &CatanMove::ReceiveProgressMonopoly(_) => self.rules.cities_knights,
+            &CatanMove::Roll(_) => !self.rules.cities_knights,
+            _ => true,
+        }
+    }
+
+    fn allowed_in_state(&self, mv: &CatanMove) -> bool {
+        let building = self.state == GameState::Turn || self.state == GameState::SpecialBuilding;
+        match (mv, self.state) {
+            (&CatanMove::PlaceSettlement(_), GameState::SetupSettlements { .. }) |
+            (&CatanMove::PlaceSettlement(_), GameState::SetupCities { .. }) |
+            (&CatanMove::PlaceCity(_), GameState::SetupCities { .. }) |
+            (&CatanMove::PlaceRoad(_), GameState::SetupSettlementRoad { .. }) |
+            (&CatanMove::PlaceRoad(_), GameState::SetupCityRoad { .. }) |
+            (&CatanMove::PlaceRoad(_), GameState::ResolvingDevCard(DevCard::RoadBuilding, _)) |
+            (&CatanMove::Roll(_), GameState::Roll) |
+            (&CatanMove::RollDice { .. }, GameState::Roll) |
+            (&CatanMove::Discard(_), GameState::Discarding { .. }) |
+            (&CatanMove::ChooseGold(_), GameState::ChoosingGold { .. }) |
+            (&CatanMove::MoveRobber(..), GameState::MovingRobber) |
+            (&CatanMove::MovePirate(..), GameState::MovingRobber) |
+            (&CatanMove::Steal(..), GameState::StealingCards(_)) |
+            (&CatanMove::LoseCity(_), GameState::PillagingCity { .. }) |
+            (&CatanMove::DrawDevCard(_), GameState::DrawingDevCard) |
+            (&CatanMove::PlayDevCard(DevCard::Soldier), GameState::PreRoll) |
+            (&CatanMove::PlayDevCard(_), GameState::Turn) |
+            (&CatanMove::ReceiveMonopoly(_), GameState::ResolvingDevCard(DevCard::Monopoly, _)) |
+            (&CatanMove::ReceiveYearOfPlenty(..), GameState::ResolvingDevCard(DevCard::YearOfPlenty, _)) |
+            (&CatanMove::DrawProgressCard(_), GameState::DrawingProgressCard { .. }) |
+            (&CatanMove::PlayProgressCard(_), GameState::Turn) |
+            (&CatanMove::ReceiveProgressMonopoly(_), GameState::ResolvingProgressCard(_)) |
+            (&CatanMove::ChaseRobber(_), GameState::Turn) |
+            (&CatanMove::OfferTrade { .. }, GameState::Turn) |
+            (&CatanMove::AcceptTrade, GameState::TradeOffered { .. }) |
+            (&CatanMove::CounterTrade { .. }, GameState::TradeOffered { .. }) |
+            (&CatanMove::RejectTrade, GameState::TradeOffered { .. }) |
+            (&CatanMove::RejectTrade, GameState::TradeCountered { .. }) |
+            (&CatanMove::Trade { .. }, GameState::TradeCountered { .. }) |
+            (&CatanMove::Convert4x { .. }, GameState::Turn) |
+            (&CatanMove::Convert3x { .. }, GameState::Turn) |
+            (&CatanMove::Convert2x { .. }, GameState::Turn) => true,
+
+            (&CatanMove::EndTurn, _) |
+            (&CatanMove::BuildRoad(_), _) |
+            (&CatanMove::BuildSettlement(_), _) |
+            (&CatanMove::BuildCity(_), _) |
+            (&CatanMove::BuildShip(_), _) |
+            (&CatanMove::BuildKnight(_), _) |
+            (&CatanMove::ActivateKnight(_), _) |
+            (&CatanMove::PromoteKnight(_), _) |
+            (&CatanMove::ImproveCity(_), _) |
+            (&CatanMove::BuyDevCard, _) => building,
+
+            // series and state changes only ever come from the list of available moves
+            _ => false,
+        }
+    }
+
+    fn check_vertex_free(&self, vertex_id: VertexID) -> Result<(), IllegalMove> {
+        if self.vertices.get(&vertex_id).unwrap().structure.is_some() {
+            Err(IllegalMove::VertexOccupied(vertex_id))
+        } else if !self.settlement_location_is_valid(vertex_id) {
+            Err(IllegalMove::TooCloseToSettlement(vertex_id))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_edge_free(&self, edge_id: EdgeID) -> Result<(), IllegalMove> {
+        if self.edges.get(&edge_id).unwrap().is_empty() {
+            Ok(())
+        } else {
+            Err(IllegalMove::EdgeOccupied(edge_id))
+        }
+    }
+
+    // The current player's knight at `vertex_id`, as (level, active)
+    fn own_knight(&self, vertex_id: VertexID) -> Result<(u8, bool), IllegalMove> {
+        match self.vertices.get(&vertex_id).unwrap().structure {
+            Some((Structure::Knight { level, active }, owner)) if owner == self.cur_player => Ok((level, active)),
+            _ => Err(IllegalMove::NotOwned(vertex_id)),
+        }
+    }
+
+    // Returns the first specific reason the move can't be made, if there is one
+    fn diagnose(&self, mv: &CatanMove) -> Result<(), IllegalMove> {
+        let player = self.players.get(&self.cur_player).unwrap().borrow();
+
+        match mv {
+            &CatanMove::PlaceSettlement(vertex) | &CatanMove::PlaceCity(vertex) => self.check_vertex_free(vertex),
+            &CatanMove::PlaceRoad(edge) => {
+                self.check_edge_free(edge)?;
+                Err(IllegalMove::NotConnected)
+            }
+
+            &CatanMove::Roll(_) | &CatanMove::RollDice { .. } | &CatanMove::DrawDevCard(_) |
+            &CatanMove::DrawProgressCard(_) | &CatanMove::Steal(..) => Err(IllegalMove::ImpossibleOutcome),
+            &CatanMove::Discard(ref cards) => {
+                let expected = player.num_cards() / 2;
+                if num_cards(cards) != expected {
+                    return Err(IllegalMove::WrongNumberOfCards { expected, got: num_cards(cards) });
+                }
+                pay(&player, cards)
+            }
+            &CatanMove::ChooseGold(ref cards) => {
+                let owed = self.gold_owed.first().map(|&(_, num)| num).unwrap_or(0);
+                if num_cards(cards) > owed {
+                    return Err(IllegalMove::WrongNumberOfCards { expected: owed, got: num_cards(cards) });
+                }
+                if cards.iter().any(|&(res, num)| self.bank[res as usize] < num) {
+                    return Err(IllegalMove::BankEmpty);
+                }
+                Ok(())
+            }
+            &CatanMove::MoveRobber(pos, _) => {
+                if pos == self.robber_pos || self.is_water(pos) || !self.robber_allowed(&pos) {
+                    return Err(IllegalMove::InvalidRobberPosition(pos));
+                }
+                Ok(())
+            }
+            &CatanMove::MovePirate(pos, _) => {
+                if Some(pos) == self.pirate_pos || !self.is_water(pos) {
+                    return Err(IllegalMove::InvalidRobberPosition(pos));
+                }
+                Ok(())
+            }
+            &CatanMove::LoseCity(vertex) => {
+                match self.vertices.get(&vertex).unwrap().structure {
+                    Some((Structure::City, owner)) if owner == self.cur_player => Ok(()),
+                    _ => Err(IllegalMove::NotOwned(vertex)),
+                }
+            }
+
+            &CatanMove::BuildRoad(edge) => {
+                self.check_edge_free(edge)?;
+                if player.roads_left == 0 {
+                    return Err(IllegalMove::NoPiecesLeft);
+                }
+                pay(&player, &ROAD_COST)?;
+                Err(IllegalMove::NotConnected)
+            }
+            &CatanMove::BuildShip(edge) => {
+                self.check_edge_free(edge)?;
+                if player.ships_left == 0 {
+                    return Err(IllegalMove::NoPiecesLeft);
+                }
+                pay(&player, &SHIP_COST)?;
+                Err(IllegalMove::NotConnected)
+            }
+            &CatanMove::BuildSettlement(vertex) => {
+                self.check_vertex_free(vertex)?;
+                if player.settlements_left == 0 {
+                    return Err(IllegalMove::NoPiecesLeft);
+                }
+                pay(&player, &SETTLEMENT_COST)?;
+                Err(IllegalMove::NotConnected)
+            }
+            &CatanMove::BuildCity(vertex) => {
+                if !player.settlements.contains(&vertex) {
+                    return Err(IllegalMove::NotOwned(vertex));
+                }
+                if player.cities_left == 0 {
+                    return Err(IllegalMove::NoPiecesLeft);
+                }
+                pay(&player, &CITY_COST)
+            }
+            &CatanMove::BuildKnight(vertex) => {
+                if self.vertices.get(&vertex).unwrap().structure.is_some() {
+                    return Err(IllegalMove::VertexOccupied(vertex));
+                }
+                if player.knights_left[0] == 0 {
+                    return Err(IllegalMove::NoPiecesLeft);
+                }
+                pay(&player, &KNIGHT_COST)?;
+                Err(IllegalMove::NotConnected)
+            }
+            &CatanMove::ActivateKnight(vertex) => {
+                self.own_knight(vertex)?;
+                pay(&player, &ACTIVATE_COST)
+            }
+            &CatanMove::PromoteKnight(vertex) => {
+                let (level, _) = self.own_knight(vertex)?;
+                if level as usize >= player.knights_left.len() || player.knights_left[level as usize] == 0 {
+                    return Err(IllegalMove::NoPiecesLeft);
+                }
+                pay(&player, &KNIGHT_COST)
+            }
+            &CatanMove::ChaseRobber(vertex) => self.own_knight(vertex).map(|_| ()),
+            &CatanMove::ImproveCity(track) => {
+                let level = player.improvements[track as usize];
+                if level < MAX_IMPROVEMENT_LEVEL && player.get_resource(track.commodity()) <= level {
+                    Err(IllegalMove::InsufficientResources)
+                } else {
+                    Ok(())
+                }
+            }
+
+            &CatanMove::BuyDevCard => {
+                pay(&player, &DEV_CARD_COST)?;
+                if self.dev_cards_left() == 0 {
+                    return Err(IllegalMove::DeckEmpty);
+                }
+                Ok(())
+            }
+            &CatanMove::PlayDevCard(card) => {
+                if !player.dev_cards.contains(&card) || card == DevCard::VictoryPoint {
+                    return Err(IllegalMove::CardNotHeld);
+                }
+                if self.dev_card_played {
+                    return Err(IllegalMove::DevCardAlreadyPlayed);
+                }
+                Ok(())
+            }
+            &CatanMove::ReceiveYearOfPlenty(first, second) => {
+                let needed = if first == second { 2 } else { 1 };
+                if self.bank[first as usize] < needed || self.bank[second as usize] < needed {
+                    return Err(IllegalMove::BankEmpty);
+                }
+                Ok(())
+            }
+            &CatanMove::PlayProgressCard(card) => {
+                if player.progress_cards.contains(&card) {
+                    Ok(())
+                } else {
+                    Err(IllegalMove::CardNotHeld)
+                }
+            }
+
+            &CatanMove::OfferTrade { ref give, .. } => {
+                if self.trades_offered >= MAX_TRADE_OFFERS_PER_TURN {
+                    return Err(IllegalMove::TooManyTradeOffers);
+                }
+                pay(&player, give)
+            }
+            &CatanMove::AcceptTrade => {
+                match self.pending_trade {
+                    Some(ref offer) => pay(&player, &offer.take),
+                    None => Ok(()),
+                }
+            }
+            &CatanMove::Convert4x { from, to } => self.check_conversion(&player, from, to, 4),
+            &CatanMove::Convert3x { from, to } => {
+                if !player.has_3to1_port {
+                    return Err(IllegalMove::NoPort);
+                }
+                self.check_conversion(&player, from, to, 3)
+            }
+            &CatanMove::Convert2x { from, to } => {
+                if !player.ports.contains(&from) {
+                    return Err(IllegalMove::NoPort);
+                }
+                self.check_conversion(&player, from, to, 2)
+            }
+
+            _ => Ok(()),
+        }
+    }
+
+    fn check_conversion(&self, player: &Player, from: Resource, to: Resource, rate: u8) -> Result<(), IllegalMove> {
+        pay(player, &[(from, rate)])?;
+        if self.bank[to as usize] == 0 {
+            return Err(IllegalMove::BankEmpty);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ai::Game;
+    use catan::*;
+    use catan::testing::*;
+
+    fn first_turn() -> Catan {
+        let mut catan = game_with(CatanRules::base_game());
+        let mut rng = seeded_rng(7);
+        while catan.state != GameState::Turn {
+            let mv = random_move(&catan, &mut rng).unwrap();
+            catan.make_move(&mv);
+        }
+        catan
+    }
+
+    #[test]
+    fn stealing_from_an_empty_hand_takes_nothing() {
+        let mut catan = first_turn();
+        let thief = catan.cur_player;
+        let victim = catan.players[&thief].borrow().static_data.next_player;
+        catan.players[&victim].borrow_mut().cards.clear();
+        let thief_cards = catan.players[&thief].borrow().num_cards();
+        let bank = catan.bank;
+        catan.state = GameState::StealingCards(victim);
+
+        let steal = CatanMove::Steal(Resource::Wheat, victim);
+        assert_eq!(catan.validate_move(&steal), Err(IllegalMove::ImpossibleOutcome));
+
+        catan.make_move(&steal);
+        assert_eq!(catan.players[&thief].borrow().num_cards(), thief_cards);
+        assert_eq!(catan.players[&victim].borrow().num_cards(), 0);
+        assert_eq!(catan.bank, bank);
+        assert_eq!(catan.state, GameState::Turn);
+    }
+
+    #[test]
+    fn moves_in_the_wrong_phase_are_illegal() {
+        let catan = game_with(CatanRules::base_game());
+        let state = catan.state;
+        let edge = *catan.edges.keys().next().unwrap();
+        assert_eq!(catan.validate_move(&CatanMove::BuildRoad(edge)), Err(IllegalMove::WrongPhase(state)));
+        assert_eq!(catan.validate_move(&CatanMove::EndTurn), Err(IllegalMove::WrongPhase(state)));
+        assert_eq!(catan.validate_move(&CatanMove::BuyDevCard), Err(IllegalMove::WrongPhase(state)));
+    }
+
+    #[test]
+    fn taken_vertices_are_illegal() {
+        let mut catan = game_with(CatanRules::base_game());
+        let mut rng = seeded_rng(3);
+        let vertex = match random_move(&catan, &mut rng) {
+            Some(CatanMove::PlaceSettlement(vertex)) => vertex,
+            other => panic!("expected a settlement, not {:?}", other),
+        };
+        catan.make_move(&CatanMove::PlaceSettlement(vertex));
+        let road = random_move(&catan, &mut rng).unwrap();
+        catan.make_move(&road);
+
+        let place = CatanMove::PlaceSettlement(vertex);
+        assert_eq!(catan.validate_move(&place), Err(IllegalMove::VertexOccupied(vertex)));
+        assert_eq!(catan.try_make_move(&place), Err(IllegalMove::VertexOccupied(vertex)));
+
+        let edge = catan.vertices[&vertex].static_data.edges.iter().filter_map(|edge| *edge).next().unwrap();
+        let neighbor = catan.edges[&edge].static_data.vertices.iter().cloned().find(|other| *other != vertex).unwrap();
+        assert_eq!(catan.validate_move(&CatanMove::PlaceSettlement(neighbor)), Err(IllegalMove::TooCloseToSettlement(neighbor)));
+    }
+
+    #[test]
+    fn building_without_the_cards_is_illegal() {
+        let mut catan = first_turn();
+        let player_id = catan.cur_player;
+        catan.players[&player_id].borrow_mut().cards.clear();
+
+        let (edges, _) = catan.players[&player_id].borrow().get_buildable_spaces(&catan);
+        let edge = *edges.iter().min().unwrap();
+        let settlement = *catan.players[&player_id].borrow().settlements.iter().min().unwrap();
+
+        assert_eq!(catan.validate_move(&CatanMove::BuildRoad(edge)), Err(IllegalMove::InsufficientResources));
+        assert_eq!(catan.validate_move(&CatanMove::BuildCity(settlement)), Err(IllegalMove::InsufficientResources));
+        assert_eq!(catan.validate_move(&CatanMove::BuyDevCard), Err(IllegalMove::InsufficientResources));
+    }
+
+    #[test]
+    fn unknown_ids_are_illegal() {
+        let catan = first_turn();
+        let far_away = HexCoord::new(40, 40);
+        assert_eq!(catan.validate_move(&CatanMove::BuildSettlement(99999)), Err(IllegalMove::UnknownVertex(99999)));
+        assert_eq!(catan.validate_move(&CatanMove::BuildRoad(99999)), Err(IllegalMove::UnknownEdge(99999)));
+        assert_eq!(catan.validate_move(&CatanMove::MoveRobber(far_away, None)), Err(IllegalMove::UnknownHex(far_away)));
+        assert_eq!(catan.validate_move(&CatanMove::Steal(Resource::Wheat, 42)), Err(IllegalMove::UnknownPlayer(42)));
+    }
+}
@@ -5,8 +5,25 @@ mod hex;
 mod edge;
 mod vertex;
 mod board_builder;
+mod board_generator;
+mod board_layout;
+mod rules;
+mod cities_knights;
+mod save;
+mod record;
+mod undo;
+mod legality;
+#[cfg(test)]
+mod testing;
 
 pub use catan::catan::*;
 pub use catan::hex_coord::*;
 pub use catan::hex::HexType;
-pub use catan::board_builder::BoardBuilder;
\ No newline at end of file
+pub use catan::board_builder::BoardBuilder;
+pub use catan::board_generator::{ BoardConstraints, GenerationError };
+pub use catan::board_layout::LayoutError;
+pub use catan::record::GameRecord;
+pub use catan::undo::Undo;
+pub use catan::legality::IllegalMove;
+pub use catan::rules::*;
+pub use catan::cities_knights::*;
\ No newline at end of file
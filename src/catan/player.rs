@@ -8,6 +8,7 @@ use cairo::Context;
 use catan::*;
 
 pub struct PlayerStatic {
+	pub id: PlayerID,
     pub color: [f64; 3],
 	pub next_player: PlayerID,
 	pub prev_player: PlayerID,
@@ -19,13 +20,28 @@ pub struct Player {
 
     pub victory_points: u8,
     pub settlements: HashSet<VertexID>,
+	pub cities: HashSet<VertexID>,
     pub roads: HashSet<EdgeID>,
+    pub ships: HashSet<EdgeID>,
     pub ports: HashSet<Resource>,
 	pub has_3to1_port: bool,
 	pub soldiers: u8,
 
+	// pieces that haven't been placed on the board yet
+	pub roads_left: u8,
+	pub settlements_left: u8,
+	pub cities_left: u8,
+	pub ships_left: u8,
+	pub knights_left: [u8; 3], // indexed by level - 1
+
+	pub islands: HashSet<usize>, // islands this player has earned a bonus for settling
+
     pub cards: HashMap<Resource, u8>,
     pub dev_cards: Vec<DevCard>,
+
+	// Cities & Knights
+	pub improvements: [u8; 3], // indexed by Improvement
+	pub progress_cards: Vec<ProgressCard>,
 }
 
 fn print(ctx: &Context, color: [f64; 3], text: &str) {
@@ -34,19 +50,32 @@ fn print(ctx: &Context, color: [f64; 3], text: &str) {
 }
   
 impl Player {
-	pub fn new(static_data: PlayerStatic) -> Self {
+	pub fn new(static_data: PlayerStatic, rules: &CatanRules) -> Self {
 		Player {
 			static_data: Arc::new(static_data),
 
-			victory_points: 3, // includes starting city and settlement
+			victory_points: if rules.setup_city { 3 } else { 2 }, // includes the starting buildings
 			settlements: HashSet::new(),
+			cities: HashSet::new(),
 			roads: HashSet::new(),
+			ships: HashSet::new(),
 			ports: HashSet::new(),
 			has_3to1_port: false,
 			soldiers: 0,
 
+			roads_left: rules.max_roads,
+			settlements_left: rules.max_settlements,
+			cities_left: rules.max_cities,
+			ships_left: rules.max_ships,
+			knights_left: [KNIGHTS_PER_LEVEL; 3],
+
+			islands: HashSet::new(),
+
 			cards: HashMap::new(),
 			dev_cards: Vec::new(),
+
+			improvements: [0; 3],
+			progress_cards: Vec::new(),
 		}
 	}
 
@@ -54,10 +83,20 @@ impl Player {
 		self.static_data.color
 	}
 
+	// public points plus any victory point cards hidden in the player's hand
+	pub fn total_victory_points(&self) -> u8 {
+		let hidden = self.dev_cards.iter().filter(|card| **card == DevCard::VictoryPoint).count();
+		self.victory_points + hidden as u8
+	}
+
 	pub fn get_resource(&self, resource: Resource) -> u8 {
 		*self.cards.get(&resource).unwrap_or(&0)
 	}
 
+	pub fn num_cards(&self) -> u8 {
+		self.cards.values().fold(0, |sum, num| sum.saturating_add(*num))
+	}
+
 	pub fn consume_resource(&mut self, resource: Resource, n: u8) {
 		*self.cards.get_mut(&resource).unwrap() -= n;
 	}
@@ -69,85 +108,119 @@ impl Player {
 		}
 	}
 
-	fn path(&self, catan: &Catan, first_edge: EdgeID, visited_edges: &mut HashSet<EdgeID>, path: &mut u8, max_path: &mut u8) {
-		visited_edges.insert(first_edge);
+	// Length of the longest trail that starts by walking along `edge_id` away from `from`.
+	// Trails can't continue through a vertex where another player has built, and can only
+	// switch between roads and ships at one of the player's own settlements or cities.
+	fn trail(&self, catan: &Catan, player_id: PlayerID, edge_id: EdgeID, from: VertexID, visited_edges: &mut HashSet<EdgeID>) -> u8 {
+		visited_edges.insert(edge_id);
 
-		let edge = catan.edges.get(&first_edge).unwrap();
-		for vertex_id in edge.static_data.vertices.iter() {
-			let vertex = catan.vertices.get(&vertex_id).unwrap();
-			for edge_id in vertex.static_data.edges.iter().filter_map(|x| *x) {
-				if self.roads.contains(&edge_id) && !visited_edges.contains(&edge_id) {
-					*path += 1;
-					*max_path = (*max_path).max(*path);
-					self.path(catan, edge_id, visited_edges, path, max_path);
-					*path -= 1;
+		let edge = catan.edges.get(&edge_id).unwrap();
+		let to = if edge.static_data.vertices[0] == from {
+			edge.static_data.vertices[1]
+		} else {
+			edge.static_data.vertices[0]
+		};
+		let vertex = catan.vertices.get(&to).unwrap();
+
+		let (blocked, owned) = match vertex.structure {
+			Some((_, owner)) => (owner != player_id, owner == player_id),
+			None => (false, false),
+		};
+		let on_ship = self.ships.contains(&edge_id);
+
+		let mut longest = 1;
+		if !blocked {
+			for next_edge in vertex.static_data.edges.iter().filter_map(|x| *x) {
+				let next_is_ship = self.ships.contains(&next_edge);
+				if !self.roads.contains(&next_edge) && !next_is_ship {
+					continue;
+				}
+				if visited_edges.contains(&next_edge) || (next_is_ship != on_ship && !owned) {
+					continue;
 				}
+				longest = longest.max(1 + self.trail(catan, player_id, next_edge, to, visited_edges));
 			}
 		}
 
-		visited_edges.remove(&first_edge);
+		visited_edges.remove(&edge_id);
+		longest
 	}
 
-	pub fn get_longest_road(&self, catan: &Catan, starting_road: EdgeID) -> u8 {
+	pub fn get_longest_road(&self, catan: &Catan, player_id: PlayerID) -> u8 {
 		let mut visited_edges = HashSet::new();
-		let mut path = 0;
-		let mut max_path = 0;
-		self.path(catan, starting_road, &mut visited_edges, &mut path, &mut max_path);
-		max_path
+		let mut longest = 0;
+		for edge_id in self.roads.iter().chain(self.ships.iter()) {
+			let edge = catan.edges.get(edge_id).unwrap();
+			for vertex_id in edge.static_data.vertices.iter() {
+				longest = longest.max(self.trail(catan, player_id, *edge_id, *vertex_id, &mut visited_edges));
+			}
+		}
+		longest
 	}
 
+	// Roads can be built next to the player's settlements and cities, and next to the ends of
+	// their roads unless someone else has built there, but never out at sea. Settlements and
+	// knights can go on any empty vertex the roads reach; the distance rule is checked separately.
 	pub fn get_buildable_spaces(&self, catan: &Catan) -> (HashSet<EdgeID>, HashSet<VertexID>) {
-		let mut visited_edges = HashSet::new();
-		let mut visited_vertices = HashSet::new();
-		let mut buildable_edges = HashSet::new();
+		let player_id = self.static_data.id;
+		let mut anchors: HashSet<VertexID> = self.settlements.union(&self.cities).cloned().collect();
 		let mut buildable_vertices = HashSet::new();
 
-		// For each road this player owns:
-		//   get the two vertices ajacent to it
-		//     if nothing is built on that vertex, mark it as buildable
-		//   get the four edges ajacent to it (really, that are ajacent to the 2 vertices)
-		//     if nothing is built on that edge, mark it as buildable
-		// this should mark all edges and vertices ajacent to the player's roads as buildable
-
-		// this is nested way too deep, but idk of a good way to fix it
-
-		// Iterate over the player's roads
-		for edge_id in &self.roads {
-			if !visited_edges.contains(edge_id) {
-				visited_edges.insert(*edge_id);
-
-				if let Some(edge) = catan.get_edge(edge_id) {
-					// Get the vertices ajacent to each road
-					for vertex_id in edge.static_data.vertices.iter() {
-						if !visited_vertices.contains(vertex_id) {
-							visited_vertices.insert(*vertex_id);
-
-							if let Some(vertex) = catan.get_vertex(vertex_id) {
-								// Mark the vertex as buildable if there is nothing built on it
-								if vertex.structure.is_none() {
-									buildable_vertices.insert(*vertex_id);
-								}
-
-								// Get the edges ajacent to each road
-								for ajacent_edge_id in vertex.static_data.edges.iter().filter_map(|x| *x) {
-									if !visited_edges.contains(&ajacent_edge_id) {
-										visited_edges.insert(ajacent_edge_id);
-
-										if let Some(ajacent_edge) = catan.get_edge(&ajacent_edge_id) {
-											// Mark the edge as buildable if there is nothing built on it
-											if ajacent_edge.road.is_none() {
-												buildable_edges.insert(ajacent_edge_id);
-											}
-										}
-									}
-								}
-							}
-						}
+		for edge_id in self.roads.iter() {
+			let edge = catan.get_edge(edge_id).unwrap();
+			for vertex_id in edge.static_data.vertices.iter() {
+				match catan.get_vertex(vertex_id).unwrap().structure {
+					// roads can't continue through another player's settlement, city or knight
+					Some((_, owner)) if owner != player_id => { }
+					Some(_) => { anchors.insert(*vertex_id); }
+					None => {
+						anchors.insert(*vertex_id);
+						buildable_vertices.insert(*vertex_id);
 					}
 				}
 			}
 		}
 
+		let mut buildable_edges = HashSet::new();
+		for vertex_id in anchors.iter() {
+			let vertex = catan.get_vertex(vertex_id).unwrap();
+			for edge_id in vertex.static_data.edges.iter().filter_map(|x| *x) {
+				if catan.get_edge(&edge_id).unwrap().is_empty() && !catan.is_bridge(edge_id) {
+					buildable_edges.insert(edge_id);
+				}
+			}
+		}
+
+		(buildable_edges, buildable_vertices)
+	}
+
+	// Ships go on the coast or out at sea, and have to connect to one of the player's
+	// settlements or cities or to the open end of one of their other ships. Settlements
+	// can also be built at the end of a ship.
+	pub fn get_buildable_ship_spaces(&self, catan: &Catan) -> (HashSet<EdgeID>, HashSet<VertexID>) {
+		let mut anchors: HashSet<VertexID> = self.settlements.union(&self.cities).cloned().collect();
+		let mut buildable_vertices = HashSet::new();
+
+		for edge_id in self.ships.iter() {
+			let edge = catan.get_edge(edge_id).unwrap();
+			for vertex_id in edge.static_data.vertices.iter() {
+				if catan.get_vertex(vertex_id).unwrap().structure.is_none() {
+					anchors.insert(*vertex_id);
+					buildable_vertices.insert(*vertex_id);
+				}
+			}
+		}
+
+		let mut buildable_edges = HashSet::new();
+		for vertex_id in anchors.iter() {
+			let vertex = catan.get_vertex(vertex_id).unwrap();
+			for edge_id in vertex.static_data.edges.iter().filter_map(|x| *x) {
+				if catan.get_edge(&edge_id).unwrap().is_empty() && catan.ship_allowed(edge_id) {
+					buildable_edges.insert(edge_id);
+				}
+			}
+		}
+
 		(buildable_edges, buildable_vertices)
 	}
 }
@@ -162,5 +235,192 @@ impl Hash for Player {
 		for card in self.dev_cards.iter() {
 			card.hash(state);
 		}
+		self.improvements.hash(state);
+		for card in self.progress_cards.iter() {
+			card.hash(state);
+		}
+
+		let mut islands: Vec<&usize> = self.islands.iter().collect();
+		islands.sort();
+		islands.hash(state);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+
+	use ai::{ Game, MoveList };
+	use catan::*;
+	use catan::vertex::Structure;
+
+	const WATER: HexType = HexType::Water;
+	const LAND: HexType = HexType::Land(Resource::Wheat);
+
+	// A board of just these hexes, with two players
+	fn board(hexes: &[(i16, i16, HexType)]) -> Catan {
+		let mut builder = BoardBuilder::new();
+		for &(q, r, typ) in hexes.iter() {
+			builder.add_hex(HexCoord::new(q, r), typ, if typ == LAND { 6 } else { 0 });
+		}
+		let p1 = builder.add_player([1.0, 0.0, 0.0]);
+		let p2 = builder.add_player([0.0, 0.0, 1.0]);
+		builder.set_player_order(vec![p1, p2]);
+		Catan::new(builder)
+	}
+
+	// The corners of a hex, going round it; each is joined to the next by a side
+	fn corners(catan: &Catan, q: i16, r: i16) -> [VertexID; 6] {
+		catan.hexes[&HexCoord::new(q, r)].static_data.vertices
+	}
+
+	fn edge_between(catan: &Catan, a: VertexID, b: VertexID) -> EdgeID {
+		*catan.edges.iter()
+			.find(|&(_, edge)| edge.static_data.vertices.contains(&a) && edge.static_data.vertices.contains(&b))
+			.unwrap().0
+	}
+
+	fn edges_at(catan: &Catan, vertex: VertexID) -> HashSet<EdgeID> {
+		catan.vertices[&vertex].static_data.edges.iter().filter_map(|edge| *edge).collect()
+	}
+
+	fn build(catan: &mut Catan, vertex: VertexID, structure: Structure, player_id: PlayerID) {
+		catan.vertices.get_mut(&vertex).unwrap().structure = Some((structure, player_id));
+		if let Structure::Settlement = structure {
+			catan.players[&player_id].borrow_mut().settlements.insert(vertex);
+		}
+		if let Structure::City = structure {
+			catan.players[&player_id].borrow_mut().cities.insert(vertex);
+		}
+	}
+
+	fn road(catan: &mut Catan, from: VertexID, to: VertexID, player_id: PlayerID) -> EdgeID {
+		let edge = edge_between(catan, from, to);
+		catan.edges.get_mut(&edge).unwrap().road = Some(player_id);
+		catan.players[&player_id].borrow_mut().roads.insert(edge);
+		edge
+	}
+
+	fn buildable(catan: &Catan, player_id: PlayerID) -> (HashSet<EdgeID>, HashSet<VertexID>) {
+		catan.players[&player_id].borrow().get_buildable_spaces(catan)
+	}
+
+	fn set(items: &[usize]) -> HashSet<usize> {
+		items.iter().cloned().collect()
+	}
+
+	#[test]
+	fn roads_start_from_own_settlements_and_cities() {
+		let mut catan = board(&[(0, 0, LAND)]);
+		let v = corners(&catan, 0, 0);
+		assert_eq!(buildable(&catan, 1), (HashSet::new(), HashSet::new()));
+
+		build(&mut catan, v[0], Structure::Settlement, 1);
+		assert_eq!(buildable(&catan, 1).0, edges_at(&catan, v[0]));
+
+		build(&mut catan, v[3], Structure::City, 1);
+		let both: HashSet<EdgeID> = edges_at(&catan, v[0]).union(&edges_at(&catan, v[3])).cloned().collect();
+		assert_eq!(buildable(&catan, 1), (both, HashSet::new()));
+
+		// other players' buildings and the player's own knights are no use
+		assert_eq!(buildable(&catan, 2), (HashSet::new(), HashSet::new()));
+		let mut knights_only = board(&[(0, 0, LAND)]);
+		build(&mut knights_only, v[0], Structure::Knight { level: 1, active: true }, 1);
+		assert_eq!(buildable(&knights_only, 1), (HashSet::new(), HashSet::new()));
+	}
+
+	#[test]
+	fn roads_end_at_other_players_buildings() {
+		let blockers = [Structure::Settlement, Structure::City, Structure::Knight { level: 1, active: false }];
+		for (n, blocker) in blockers.iter().enumerate() {
+			let mut catan = board(&[(0, 0, LAND)]);
+			let v = corners(&catan, 0, 0);
+			let built = road(&mut catan, v[0], v[1], 1);
+			let behind = edge_between(&catan, v[1], v[2]);
+
+			let (edges, vertices) = buildable(&catan, 1);
+			assert!(edges.contains(&behind));
+			assert_eq!(vertices, set(&[v[0], v[1]]));
+
+			build(&mut catan, v[1], *blocker, 2);
+			let (edges, vertices) = buildable(&catan, 1);
+			let mut expected = edges_at(&catan, v[0]);
+			expected.remove(&built);
+			assert_eq!(edges, expected, "blocker {}", n);
+			assert_eq!(vertices, set(&[v[0]]));
+		}
+
+		// the player's own buildings don't get in the way
+		let mut catan = board(&[(0, 0, LAND)]);
+		let v = corners(&catan, 0, 0);
+		road(&mut catan, v[0], v[1], 1);
+		build(&mut catan, v[1], Structure::Settlement, 1);
+		assert!(buildable(&catan, 1).0.contains(&edge_between(&catan, v[1], v[2])));
+	}
+
+	#[test]
+	fn no_roads_between_water_hexes() {
+		// corner 0 of the land hex touches both water hexes, and the side between them is all water
+		let mut catan = board(&[(0, 0, LAND), (1, 0, WATER), (1, -1, WATER)]);
+		let v = corners(&catan, 0, 0);
+		let coast = set(&[edge_between(&catan, v[5], v[0]), edge_between(&catan, v[0], v[1])]);
+		let at_sea: Vec<EdgeID> = edges_at(&catan, v[0]).difference(&coast).cloned().collect();
+		assert_eq!(at_sea.len(), 1);
+		assert!(catan.is_bridge(at_sea[0]));
+
+		build(&mut catan, v[0], Structure::Settlement, 1);
+		assert_eq!(buildable(&catan, 1).0, coast);
+
+		// a road along the coast can't carry on out past the water hex either
+		road(&mut catan, v[0], v[1], 1);
+		let (edges, _) = buildable(&catan, 1);
+		assert!(edges.iter().all(|edge| !catan.is_bridge(*edge)));
+		assert!(edges.contains(&edge_between(&catan, v[1], v[2])));
+		assert_eq!(edges.len(), 2);
+
+		// the setup phase plays by the same rules
+		let choices = |catan: &Catan| match catan.available_moves() {
+			MoveList::Choice(mvs) => mvs.into_iter().map(|mv| match mv {
+				CatanMove::PlaceRoad(edge) => edge,
+				other => panic!("expected a road, not {:?}", other),
+			}).collect::<HashSet<EdgeID>>(),
+			MoveList::Random(_) => panic!("placing a road isn't random"),
+		};
+		let mut setup = board(&[(0, 0, LAND), (1, 0, WATER), (1, -1, WATER)]);
+		build(&mut setup, v[0], Structure::Settlement, 1);
+		setup.state = GameState::SetupSettlementRoad { first_player: 1, settlement: v[0] };
+		assert_eq!(choices(&setup), coast);
+		setup.state = GameState::SetupCityRoad { first_player: 1, city: v[0] };
+		assert_eq!(choices(&setup), coast);
+	}
+
+	#[test]
+	fn settlements_go_at_the_end_of_the_players_own_roads() {
+		let mut catan = board(&[(0, 0, LAND), (1, 0, LAND)]);
+		let v = corners(&catan, 0, 0);
+		build(&mut catan, v[3], Structure::Settlement, 1);
+		road(&mut catan, v[3], v[4], 1);
+		road(&mut catan, v[4], v[5], 1);
+
+		// someone else's road leads somewhere the player can't reach
+		road(&mut catan, v[1], v[2], 2);
+
+		assert_eq!(buildable(&catan, 1).1, set(&[v[4], v[5]]));
+		assert_eq!(buildable(&catan, 2).1, set(&[v[1], v[2]]));
+
+		// of those, only the one far enough from the settlement can actually be built on
+		catan.state = GameState::Turn;
+		catan.cur_player = 1;
+		for resource in [Resource::Wood, Resource::Brick, Resource::Sheep, Resource::Wheat].iter() {
+			catan.players[&1].borrow_mut().give_resource(*resource, 1);
+		}
+		let settlements: HashSet<VertexID> = match catan.available_moves() {
+			MoveList::Choice(mvs) => mvs.into_iter().filter_map(|mv| match mv {
+				CatanMove::BuildSettlement(vertex) => Some(vertex),
+				_ => None,
+			}).collect(),
+			MoveList::Random(_) => panic!("building isn't random"),
+		};
+		assert_eq!(settlements, set(&[v[5]]));
+	}
+}
@@ -0,0 +1,382 @@
+// Game records, for going over AI games after the fact. A record is the save of the
+// position the game started from (see save.rs) followed by every move made, one per line:
+//
+//   move place_settlement 0 -1 2
+//   move place_road 0 -1 2
+//   move roll 8
+//   move build_road 1 -1 3
+//   move trade 2 give wheat:1 take rock:1
+//   move end_turn
+//
+// Dice rolls, card draws and steals are moves like any other, so replaying the list
+// gives back exactly the same game.
+
+use std::fs::File;
+use std::io::{ Read, Write };
+
+use ai::Game;
+use catan::*;
+use catan::board_layout::{ resource_name, malformed };
+use catan::save::*;
+
+fn event_name(event: EventDie) -> &'static str {
+    match event {
+        EventDie::Ship => "ship",
+        EventDie::Gate(track) => improvement_name(track),
+    }
+}
+
+fn parse_event(name: &str) -> Option<EventDie> {
+    match name {
+        "ship" => Some(EventDie::Ship),
+        _ => parse_improvement(name).map(EventDie::Gate),
+    }
+}
+
+// Cards are written as a single word, like `wheat:2,rock:1`, so moves can be nested
+fn cards_word(cards: &[(Resource, u8)]) -> String {
+    if cards.is_empty() {
+        return "none".to_string();
+    }
+    let cards: Vec<String> = cards.iter().map(|&(res, num)| format!("{}:{}", resource_name(res), num)).collect();
+    cards.join(",")
+}
+
+fn parse_cards_word(word: &str) -> Option<Vec<(Resource, u8)>> {
+    if word == "none" {
+        return Some(Vec::new());
+    }
+    let mut cards = Vec::new();
+    for pair in word.split(',') {
+        let mut parts = pair.splitn(2, ':');
+        match (parts.next().and_then(parse_card), parts.next().and_then(|num| num.parse().ok())) {
+            (Some(res), Some(num)) => cards.push((res, num)),
+            _ => return None,
+        }
+    }
+    Some(cards)
+}
+
+impl Catan {
+    // A readable name for a move, with vertices and edges given as `q r n` like in saves
+    pub fn format_move(&self, mv: &CatanMove) -> String {
+        match mv {
+            &CatanMove::Series(ref moves) => {
+                let moves: Vec<String> = moves.iter().map(|mv| self.format_move(mv)).collect();
+                format!("( {} )", moves.join(" ; "))
+            }
+            &CatanMove::SetState(state) => format!("set_state {}", self.state_string(state)),
+
+            &CatanMove::PlaceSettlement(vertex) => format!("place_settlement {}", self.vertex_string(vertex)),
+            &CatanMove::PlaceCity(vertex) => format!("place_city {}", self.vertex_string(vertex)),
+            &CatanMove::PlaceRoad(edge) => format!("place_road {}", self.edge_string(edge)),
+
+            &CatanMove::Roll(roll) => format!("roll {}", roll),
+            &CatanMove::RollDice { red, yellow, event } => format!("roll_dice {} {} {}", red, yellow, event_name(event)),
+            &CatanMove::EndTurn => "end_turn".to_string(),
+            &CatanMove::Discard(ref cards) => format!("discard {}", cards_word(cards)),
+            &CatanMove::ChooseGold(ref cards) => format!("choose_gold {}", cards_word(cards)),
+            &CatanMove::MoveRobber(pos, victim) => format!("move_robber {} {} {}", pos.q(), pos.r(), player_string(victim)),
+            &CatanMove::MovePirate(pos, victim) => format!("move_pirate {} {} {}", pos.q(), pos.r(), player_string(victim)),
+            &CatanMove::Steal(res, victim) => format!("steal {} {}", resource_name(res), victim),
+            &CatanMove::LoseCity(vertex) => format!("lose_city {}", self.vertex_string(vertex)),
+
+            &CatanMove::BuildRoad(edge) => format!("build_road {}", self.edge_string(edge)),
+            &CatanMove::BuildSettlement(vertex) => format!("build_settlement {}", self.vertex_string(vertex)),
+            &CatanMove::BuildCity(vertex) => format!("build_city {}", self.vertex_string(vertex)),
+            &CatanMove::BuildShip(edge) => format!("build_ship {}", self.edge_string(edge)),
+            &CatanMove::BuildKnight(vertex) => format!("build_knight {}", self.vertex_string(vertex)),
+            &CatanMove::ActivateKnight(vertex) => format!("activate_knight {}", self.vertex_string(vertex)),
+            &CatanMove::PromoteKnight(vertex) => format!("promote_knight {}", self.vertex_string(vertex)),
+            &CatanMove::ChaseRobber(vertex) => format!("chase_robber {}", self.vertex_string(vertex)),
+            &CatanMove::ImproveCity(track) => format!("improve_city {}", improvement_name(track)),
+
+            &CatanMove::BuyDevCard => "buy_dev_card".to_string(),
+            &CatanMove::DrawDevCard(card) => format!("draw_dev_card {}", dev_card_name(card)),
+            &CatanMove::PlayDevCard(card) => format!("play_dev_card {}", dev_card_name(card)),
+            &CatanMove::ReceiveMonopoly(res) => format!("receive_monopoly {}", resource_name(res)),
+            &CatanMove::ReceiveYearOfPlenty(first, second) =>
+                format!("receive_year_of_plenty {} {}", resource_name(first), resource_name(second)),
+
+            &CatanMove::DrawProgressCard(card) => format!("draw_progress_card {}", progress_card_name(card)),
+            &CatanMove::PlayProgressCard(card) => format!("play_progress_card {}", progress_card_name(card)),
+            &CatanMove::ReceiveProgressMonopoly(res) => format!("receive_progress_monopoly {}", resource_name(res)),
+
+            &CatanMove::OfferTrade { to, ref give, ref take } =>
+                format!("offer_trade {} give {} take {}", to, cards_word(give), cards_word(take)),
+            &CatanMove::AcceptTrade => "accept_trade".to_string(),
+            &CatanMove::RejectTrade => "reject_trade".to_string(),
+            &CatanMove::CounterTrade { ref give, ref take } =>
+                format!("counter_trade give {} take {}", cards_word(give), cards_word(take)),
+            &CatanMove::Trade { to, ref give, ref take } =>
+                format!("trade {} give {} take {}", to, cards_word(give), cards_word(take)),
+            &CatanMove::Convert4x { from, to } => format!("convert4 {} {}", resource_name(from), resource_name(to)),
+            &CatanMove::Convert3x { from, to } => format!("convert3 {} {}", resource_name(from), resource_name(to)),
+            &CatanMove::Convert2x { from, to } => format!("convert2 {} {}", resource_name(from), resource_name(to)),
+        }
+    }
+
+    pub fn parse_move(&self, text: &str) -> Result<CatanMove, LayoutError> {
+        self.parse_move_line(1, text)
+    }
+
+    fn parse_move_line(&self, line: usize, text: &str) -> Result<CatanMove, LayoutError> {
+        let mut words = Words::new(line, text);
+        let mv = self.parse_move_words(&mut words)?;
+        words.end()?;
+        Ok(mv)
+    }
+
+    fn parse_move_words(&self, words: &mut Words) -> Result<CatanMove, LayoutError> {
+        let resource = |words: &mut Words| words.parse("resource", parse_card);
+        let cards = |words: &mut Words| words.parse("cards", parse_cards_word);
+        let keyword = |words: &mut Words, keyword: &str| {
+            match words.word(keyword)? {
+                word if word == keyword => Ok(()),
+                word => Err(malformed(words.line, format!("expected '{}', not '{}'", keyword, word))),
+            }
+        };
+
+        Ok(match words.word("move")? {
+            "(" => {
+                let mut moves = Vec::new();
+                loop {
+                    moves.push(self.parse_move_words(words)?);
+                    match words.word("';' or ')'")? {
+                        ";" => continue,
+                        ")" => break,
+                        other => return Err(malformed(words.line, format!("expected ';' or ')', not '{}'", other))),
+                    }
+                }
+                CatanMove::Series(moves)
+            }
+            "set_state" => CatanMove::SetState(self.parse_state(words)?),
+
+            "place_settlement" => CatanMove::PlaceSettlement(words.vertex(self)?),
+            "place_city" => CatanMove::PlaceCity(words.vertex(self)?),
+            "place_road" => CatanMove::PlaceRoad(words.edge(self)?),
+
+            "roll" => CatanMove::Roll(words.number("roll")?),
+            "roll_dice" => CatanMove::RollDice {
+                red: words.number("red die")?,
+                yellow: words.number("yellow die")?,
+                event: words.parse("event", parse_event)?,
+            },
+            "end_turn" => CatanMove::EndTurn,
+            "discard" => CatanMove::Discard(cards(words)?),
+            "choose_gold" => CatanMove::ChooseGold(cards(words)?),
+            "move_robber" => CatanMove::MoveRobber(words.hex(self)?, words.player_or_none(self)?),
+            "move_pirate" => CatanMove::MovePirate(words.hex(self)?, words.player_or_none(self)?),
+            "steal" => CatanMove::Steal(resource(words)?, words.player(self)?),
+            "lose_city" => CatanMove::LoseCity(words.vertex(self)?),
+
+            "build_road" => CatanMove::BuildRoad(words.edge(self)?),
+            "build_settlement" => CatanMove::BuildSettlement(words.vertex(self)?),
+            "build_city" => CatanMove::BuildCity(words.vertex(self)?),
+            "build_ship" => CatanMove::BuildShip(words.edge(self)?),
+            "build_knight" => CatanMove::BuildKnight(words.vertex(self)?),
+            "activate_knight" => CatanMove::ActivateKnight(words.vertex(self)?),
+            "promote_knight" => CatanMove::PromoteKnight(words.vertex(self)?),
+            "chase_robber" => CatanMove::ChaseRobber(words.vertex(self)?),
+            "improve_city" => CatanMove::ImproveCity(words.parse("improvement", parse_improvement)?),
+
+            "buy_dev_card" => CatanMove::BuyDevCard,
+            "draw_dev_card" => CatanMove::DrawDevCard(words.parse("development card", parse_dev_card)?),
+            "play_dev_card" => CatanMove::PlayDevCard(words.parse("development card", parse_dev_card)?),
+            "receive_monopoly" => CatanMove::ReceiveMonopoly(resource(words)?),
+            "receive_year_of_plenty" => CatanMove::ReceiveYearOfPlenty(resource(words)?, resource(words)?),
+
+            "draw_progress_card" => CatanMove::DrawProgressCard(words.parse("progress card", parse_progress_card)?),
+            "play_progress_card" => CatanMove::PlayProgressCard(words.parse("progress card", parse_progress_card)?),
+            "receive_progress_monopoly" => CatanMove::ReceiveProgressMonopoly(resource(words)?),
+
+            "offer_trade" => {
+                let to = words.player(self)?;
+                keyword(words, "give")?;
+                let give = cards(words)?;
+                keyword(words, "take")?;
+                CatanMove::OfferTrade { to, give, take: cards(words)? }
+            }
+            "accept_trade" => CatanMove::AcceptTrade,
+            "reject_trade" => CatanMove::RejectTrade,
+            "counter_trade" => {
+                keyword(words, "give")?;
+                let give = cards(words)?;
+                keyword(words, "take")?;
+                CatanMove::CounterTrade { give, take: cards(words)? }
+            }
+            "trade" => {
+                let to = words.player(self)?;
+                keyword(words, "give")?;
+                let give = cards(words)?;
+                keyword(words, "take")?;
+                CatanMove::Trade { to, give, take: cards(words)? }
+            }
+            "convert4" => CatanMove::Convert4x { from: resource(words)?, to: resource(words)? },
+            "convert3" => CatanMove::Convert3x { from: resource(words)?, to: resource(words)? },
+            "convert2" => CatanMove::Convert2x { from: resource(words)?, to: resource(words)? },
+
+            other => return Err(malformed(words.line, format!("unknown move '{}'", other))),
+        })
+    }
+}
+
+// A whole game: where it started, and every move made since
+#[derive(Clone)]
+pub struct GameRecord {
+    start: Catan,
+    moves: Vec<CatanMove>,
+}
+
+impl GameRecord {
+    pub fn new(start: Catan) -> Self {
+        GameRecord {
+            start,
+            moves: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, mv: CatanMove) {
+        self.moves.push(mv);
+    }
+
+    pub fn moves(&self) -> &[CatanMove] {
+        &self.moves
+    }
+
+    pub fn start(&self) -> &Catan {
+        &self.start
+    }
+
+    // The game as it was after the first `num_moves` moves
+    pub fn position(&self, num_moves: usize) -> Catan {
+        let mut catan = self.start.clone();
+        for mv in self.moves.iter().take(num_moves) {
+            catan.make_move(mv);
+        }
+        catan
+    }
+
+    pub fn final_position(&self) -> Catan {
+        self.position(self.moves.len())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = self.start.to_save();
+        out.push_str("\n");
+        let mut catan = self.start.clone();
+        for mv in self.moves.iter() {
+            out.push_str(&format!("move {}\n", catan.format_move(mv)));
+            catan.make_move(mv);
+        }
+        out
+    }
+
+    // Moves are checked against the position they're made in, so a record that's been
+    // edited by hand can't quietly replay into a game that couldn't have happened
+    pub fn from_text(text: &str) -> Result<Self, LayoutError> {
+        let mut save = String::new();
+        let mut move_lines = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            if words.next() == Some("move") {
+                let start = line.find("move").unwrap() + "move".len();
+                move_lines.push((index + 1, &line[start..]));
+            } else {
+                save.push_str(line);
+            }
+            save.push('\n');
+        }
+
+        let mut record = GameRecord::new(Catan::from_save(&save)?);
+        let mut catan = record.start.clone();
+        for (line, text) in move_lines {
+            let mv = catan.parse_move_line(line, text)?;
+            if let Err(err) = catan.try_make_move(&mv) {
+                return Err(malformed(line, format!("'{}' can't be played here: {}", catan.format_move(&mv), err)));
+            }
+            record.push(mv);
+        }
+        Ok(record)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), LayoutError> {
+        File::create(path)?.write_all(self.to_text().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, LayoutError> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        GameRecord::from_text(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ai::Game;
+    use catan::*;
+    use catan::testing::*;
+
+    fn all_rules() -> [CatanRules; 3] {
+        [CatanRules::base_game(), CatanRules::seafarers(), CatanRules::cities_and_knights()]
+    }
+
+    // The record of a random game, made the same way as `play_randomly` plays it
+    fn random_record(rules: CatanRules, seed: u64, max_moves: usize) -> GameRecord {
+        let mut record = GameRecord::new(game_with(rules));
+        let mut catan = record.start().clone();
+        let mut rng = seeded_rng(seed);
+        for _ in 0..max_moves {
+            match random_move(&catan, &mut rng) {
+                Some(mv) => {
+                    catan.make_move(&mv);
+                    record.push(mv);
+                }
+                None => break,
+            }
+        }
+        record
+    }
+
+    #[test]
+    fn moves_read_back_as_written() {
+        for rules in all_rules().iter() {
+            for seed in 0..2 {
+                play_randomly(*rules, seed, 600, |catan| {
+                    for (mv, _) in sorted_moves(catan).1 {
+                        let text = catan.format_move(&mv);
+                        assert_eq!(catan.parse_move(&text).unwrap(), mv, "{}", text);
+                    }
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn records_replay_the_same_game() {
+        for rules in all_rules().iter() {
+            let record = random_record(*rules, 1, 600);
+            let text = record.to_text();
+            let replayed = GameRecord::from_text(&text).unwrap();
+            assert_eq!(replayed.moves(), record.moves());
+            assert_eq!(replayed.to_text(), text);
+            assert_eq!(replayed.final_position().to_save(), record.final_position().to_save());
+        }
+    }
+
+    #[test]
+    fn positions_follow_the_game() {
+        for rules in all_rules().iter() {
+            let record = random_record(*rules, 2, 600);
+            let mut num_moves = 0;
+            play_randomly(*rules, 2, 600, |catan| {
+                if num_moves % 50 == 0 {
+                    assert_eq!(record.position(num_moves).to_save(), catan.to_save(), "after {} moves", num_moves);
+                }
+                num_moves += 1;
+            });
+            assert!(num_moves >= record.moves().len());
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use catan::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SetupOrder {
+    Snake,    // 1-2-3-3-2-1, as in the base game
+    Repeated, // 1-2-3-1-2-3
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CatanRules {
+    pub victory_points: u8,
+    pub hand_limit: u8, // players holding more cards than this when a 7 is rolled discard half
+    pub dev_deck: [(DevCard, u8); 5],
+    pub bank_size: u8, // cards of each resource
+
+    pub max_roads: u8,
+    pub max_settlements: u8,
+    pub max_cities: u8,
+    pub max_ships: u8,
+
+    pub friendly_robber: bool, // the robber can't be placed next to players with few points
+    pub friendly_robber_points: u8, // ... which is this many or fewer
+    pub setup_order: SetupOrder,
+    pub setup_city: bool, // the second setup placement is a city instead of a settlement
+    pub special_building_phase: bool, // everyone else may build after each turn (5-6 players)
+
+    pub seafarers: bool, // ships, the pirate and gold fields
+    pub island_bonus: u8, // points for the first settlement on each island away from home
+
+    pub cities_knights: bool, // commodities, city improvements, knights and the barbarians
+    pub barbarian_distance: u8, // ship events before the barbarians attack
+    pub commodity_bank_size: u8, // cards of each commodity
+}
+
+impl CatanRules {
+    pub fn base_game() -> Self {
+        CatanRules {
+            victory_points: 10,
+            hand_limit: 7,
+            dev_deck: [
+                (DevCard::Soldier,      14),
+                (DevCard::YearOfPlenty,  2),
+                (DevCard::Monopoly,      2),
+                (DevCard::RoadBuilding,  2),
+                (DevCard::VictoryPoint,  5),
+            ],
+            bank_size: 19,
+
+            max_roads: 15,
+            max_settlements: 5,
+            max_cities: 4,
+            max_ships: 15,
+
+            friendly_robber: false,
+            friendly_robber_points: 2,
+            setup_order: SetupOrder::Snake,
+            setup_city: true,
+            special_building_phase: false,
+
+            seafarers: false,
+            island_bonus: 0,
+
+            cities_knights: false,
+            barbarian_distance: 7,
+            commodity_bank_size: 0,
+        }
+    }
+
+    // Seafarers of Catan, as played in "Heading for New Shores"
+    pub fn seafarers() -> Self {
+        CatanRules {
+            victory_points: 14,
+            seafarers: true,
+            island_bonus: 2,
+            ..CatanRules::base_game()
+        }
+    }
+
+    // Cities & Knights; progress cards replace the development cards
+    pub fn cities_and_knights() -> Self {
+        CatanRules {
+            victory_points: 13,
+            dev_deck: [
+                (DevCard::Soldier,      0),
+                (DevCard::YearOfPlenty, 0),
+                (DevCard::Monopoly,     0),
+                (DevCard::RoadBuilding, 0),
+                (DevCard::VictoryPoint, 0),
+            ],
+            cities_knights: true,
+            commodity_bank_size: 12,
+            ..CatanRules::base_game()
+        }
+    }
+
+    // The 5-6 player extension has a bigger deck and bank, and a special building phase
+    pub fn five_six_player() -> Self {
+        CatanRules {
+            dev_deck: [
+                (DevCard::Soldier,      20),
+                (DevCard::YearOfPlenty,  3),
+                (DevCard::Monopoly,      3),
+                (DevCard::RoadBuilding,  3),
+                (DevCard::VictoryPoint,  5),
+            ],
+            bank_size: 24,
+            special_building_phase: true,
+            ..CatanRules::base_game()
+        }
+    }
+
+    // Everyone starts on 3 points, so only players who haven't built anything yet are safe
+    pub fn friendly_robber() -> Self {
+        CatanRules {
+            friendly_robber: true,
+            friendly_robber_points: 3,
+            ..CatanRules::base_game()
+        }
+    }
+
+    // Start with two settlements instead of a settlement and a city
+    pub fn settlement_start() -> Self {
+        CatanRules {
+            setup_city: false,
+            ..CatanRules::base_game()
+        }
+    }
+}
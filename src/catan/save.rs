@@ -0,0 +1,776 @@
+// Saving and restoring a whole game, so positions from bug reports can be reproduced and
+// handed to the AI. A save starts with the board, in the same format as a layout file,
+// followed by the rules and then everything that has happened since setup began.
+//
+// Vertices and edges are written as a hex and a corner or side of it (`q r n`), since
+// their ids depend on the order the board happened to be built in.
+
+use std::fs::File;
+use std::io::{ Read, Write };
+use std::str::{ FromStr, SplitWhitespace };
+
+use catan::*;
+use catan::catan::TradeOffer;
+use catan::vertex::Structure;
+use catan::board_layout::{ write_layout, resource_name, parse_resource, parse_number, malformed };
+
+pub(in super) fn parse_card(name: &str) -> Option<Resource> {
+    match name {
+        "paper" => Some(Resource::Paper),
+        "cloth" => Some(Resource::Cloth),
+        "coin"  => Some(Resource::Coin),
+        _ => parse_resource(name),
+    }
+}
+
+pub(in super) fn dev_card_name(card: DevCard) -> &'static str {
+    match card {
+        DevCard::Soldier      => "soldier",
+        DevCard::YearOfPlenty => "year_of_plenty",
+        DevCard::Monopoly     => "monopoly",
+        DevCard::RoadBuilding => "road_building",
+        DevCard::VictoryPoint => "victory_point",
+    }
+}
+
+pub(in super) fn parse_dev_card(name: &str) -> Option<DevCard> {
+    match name {
+        "soldier"        => Some(DevCard::Soldier),
+        "year_of_plenty" => Some(DevCard::YearOfPlenty),
+        "monopoly"       => Some(DevCard::Monopoly),
+        "road_building"  => Some(DevCard::RoadBuilding),
+        "victory_point"  => Some(DevCard::VictoryPoint),
+        _ => None,
+    }
+}
+
+pub(in super) fn progress_card_name(card: ProgressCard) -> &'static str {
+    match card {
+        ProgressCard::Irrigation       => "irrigation",
+        ProgressCard::Mining           => "mining",
+        ProgressCard::RoadBuilding     => "road_building",
+        ProgressCard::Printer          => "printer",
+        ProgressCard::ResourceMonopoly => "resource_monopoly",
+        ProgressCard::TradeMonopoly    => "trade_monopoly",
+        ProgressCard::Bishop           => "bishop",
+        ProgressCard::Warlord          => "warlord",
+        ProgressCard::Constitution     => "constitution",
+    }
+}
+
+pub(in super) fn parse_progress_card(name: &str) -> Option<ProgressCard> {
+    match name {
+        "irrigation"        => Some(ProgressCard::Irrigation),
+        "mining"            => Some(ProgressCard::Mining),
+        "road_building"     => Some(ProgressCard::RoadBuilding),
+        "printer"           => Some(ProgressCard::Printer),
+        "resource_monopoly" => Some(ProgressCard::ResourceMonopoly),
+        "trade_monopoly"    => Some(ProgressCard::TradeMonopoly),
+        "bishop"            => Some(ProgressCard::Bishop),
+        "warlord"           => Some(ProgressCard::Warlord),
+        "constitution"      => Some(ProgressCard::Constitution),
+        _ => None,
+    }
+}
+
+pub(in super) fn improvement_name(track: Improvement) -> &'static str {
+    match track {
+        Improvement::Trade    => "trade",
+        Improvement::Politics => "politics",
+        Improvement::Science  => "science",
+    }
+}
+
+pub(in super) fn parse_improvement(name: &str) -> Option<Improvement> {
+    match name {
+        "trade"    => Some(Improvement::Trade),
+        "politics" => Some(Improvement::Politics),
+        "science"  => Some(Improvement::Science),
+        _ => None,
+    }
+}
+
+pub(in super) fn player_string(player: Option<PlayerID>) -> String {
+    player.map_or("none".to_string(), |id| id.to_string())
+}
+
+fn cards_string(cards: &[(Resource, u8)]) -> String {
+    let words: Vec<String> = cards.iter().map(|&(res, num)| format!("{}:{}", resource_name(res), num)).collect();
+    words.join(" ")
+}
+
+// The words of one line of a save
+pub(in super) struct Words<'a> {
+    pub(in super) line: usize,
+    pub(in super) words: SplitWhitespace<'a>,
+}
+
+impl<'a> Words<'a> {
+    pub(in super) fn new(line: usize, text: &'a str) -> Self {
+        Words { line, words: text.split_whitespace() }
+    }
+
+    pub(in super) fn word(&mut self, what: &str) -> Result<&'a str, LayoutError> {
+        let line = self.line;
+        self.words.next().ok_or_else(|| malformed(line, format!("missing {}", what)))
+    }
+
+    pub(in super) fn number<T: FromStr>(&mut self, what: &str) -> Result<T, LayoutError> {
+        let word = self.word(what)?;
+        parse_number(self.line, what, Some(word))
+    }
+
+    pub(in super) fn parse<T, F>(&mut self, what: &str, parse: F) -> Result<T, LayoutError> where F: Fn(&str) -> Option<T> {
+        let word = self.word(what)?;
+        parse(word).ok_or_else(|| malformed(self.line, format!("unknown {} '{}'", what, word)))
+    }
+
+    // The rest of the line, as a list of things
+    fn list<T, F>(&mut self, what: &str, parse: F) -> Result<Vec<T>, LayoutError> where F: Fn(&str) -> Option<T> {
+        let mut list = Vec::new();
+        while let Some(word) = self.words.next() {
+            list.push(parse(word).ok_or_else(|| malformed(self.line, format!("unknown {} '{}'", what, word)))?);
+        }
+        Ok(list)
+    }
+
+    // The rest of the line, as `thing:count` pairs
+    fn counts<T, F>(&mut self, what: &str, parse: F) -> Result<Vec<(T, u8)>, LayoutError> where F: Fn(&str) -> Option<T> {
+        let line = self.line;
+        self.list(what, |word| {
+            let mut parts = word.splitn(2, ':');
+            match (parts.next().and_then(|name| parse(name)), parts.next().and_then(|num| num.parse().ok())) {
+                (Some(thing), Some(num)) => Some((thing, num)),
+                _ => None,
+            }
+        }).map_err(|_| malformed(line, format!("expected {}:count pairs", what)))
+    }
+
+    fn flag(&mut self, what: &str) -> Result<bool, LayoutError> {
+        self.parse(what, |word| match word {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        })
+    }
+
+    pub(in super) fn player(&mut self, catan: &Catan) -> Result<PlayerID, LayoutError> {
+        let id = self.number("player")?;
+        if catan.players.contains_key(&id) {
+            Ok(id)
+        } else {
+            Err(malformed(self.line, format!("there is no player {}", id)))
+        }
+    }
+
+    pub(in super) fn player_or_none(&mut self, catan: &Catan) -> Result<Option<PlayerID>, LayoutError> {
+        if self.words.clone().next() == Some("none") {
+            self.words.next();
+            Ok(None)
+        } else {
+            self.player(catan).map(Some)
+        }
+    }
+
+    pub(in super) fn hex(&mut self, catan: &Catan) -> Result<HexCoord, LayoutError> {
+        let pos = HexCoord::new(self.number("q coordinate")?, self.number("r coordinate")?);
+        if catan.hexes.contains_key(&pos) {
+            Ok(pos)
+        } else {
+            Err(malformed(self.line, format!("there is no hex at {:?}", pos)))
+        }
+    }
+
+    fn hex_or_none(&mut self, catan: &Catan) -> Result<Option<HexCoord>, LayoutError> {
+        if self.words.clone().next() == Some("none") {
+            self.words.next();
+            Ok(None)
+        } else {
+            self.hex(catan).map(Some)
+        }
+    }
+
+    fn corner(&mut self) -> Result<usize, LayoutError> {
+        let n = self.number("corner")?;
+        if n < 6 {
+            Ok(n)
+        } else {
+            Err(malformed(self.line, format!("hexes only have 6 corners and sides, not {}", n)))
+        }
+    }
+
+    pub(in super) fn vertex(&mut self, catan: &Catan) -> Result<VertexID, LayoutError> {
+        let pos = self.hex(catan)?;
+        let n = self.corner()?;
+        Ok(catan.hexes.get(&pos).unwrap().static_data.vertices[n])
+    }
+
+    pub(in super) fn edge(&mut self, catan: &Catan) -> Result<EdgeID, LayoutError> {
+        let pos = self.hex(catan)?;
+        let n = self.corner()?;
+        Ok(catan.hexes.get(&pos).unwrap().static_data.edges[n])
+    }
+
+    pub(in super) fn end(&mut self) -> Result<(), LayoutError> {
+        match self.words.next() {
+            Some(extra) => Err(malformed(self.line, format!("unexpected '{}'", extra))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Catan {
+    pub(in super) fn vertex_string(&self, vertex_id: VertexID) -> String {
+        let vertex = self.vertices.get(&vertex_id).unwrap();
+        let pos = vertex.static_data.hex_position;
+        format!("{} {} {}", pos.q(), pos.r(), vertex.static_data.hex_side)
+    }
+
+    pub(in super) fn edge_string(&self, edge_id: EdgeID) -> String {
+        let edge = self.edges.get(&edge_id).unwrap();
+        let pos = edge.static_data.hex_position;
+        format!("{} {} {}", pos.q(), pos.r(), edge.static_data.hex_side)
+    }
+
+    // Islands are numbered differently every time the board is built, so they're saved as
+    // one of the vertices on them
+    fn island_string(&self, island: usize) -> String {
+        let mut vertices: Vec<String> = self.islands.iter()
+            .filter(|&(_, other)| *other == island)
+            .map(|(vertex_id, _)| self.vertex_string(*vertex_id))
+            .collect();
+        vertices.sort();
+        vertices[0].clone()
+    }
+
+    pub(in super) fn state_string(&self, state: GameState) -> String {
+        match state {
+            GameState::SetupSettlements { first_player } => format!("setup_settlements {}", first_player),
+            GameState::SetupSettlementRoad { first_player, settlement } =>
+                format!("setup_settlement_road {} {}", first_player, self.vertex_string(settlement)),
+            GameState::SetupCities { first_player } => format!("setup_cities {}", first_player),
+            GameState::SetupCityRoad { first_player, city } =>
+                format!("setup_city_road {} {}", first_player, self.vertex_string(city)),
+            GameState::PreRoll => "pre_roll".to_string(),
+            GameState::Roll => "roll".to_string(),
+            GameState::Turn => "turn".to_string(),
+            GameState::SpecialBuilding => "special_building".to_string(),
+            GameState::DrawingDevCard => "drawing_dev_card".to_string(),
+            GameState::ResolvingDevCard(card, step) => format!("resolving_dev_card {} {}", dev_card_name(card), step),
+            GameState::Discarding { roller } => format!("discarding {}", roller),
+            GameState::ChoosingGold { roller } => format!("choosing_gold {}", roller),
+            GameState::PillagingCity { roller } => format!("pillaging_city {}", roller),
+            GameState::DrawingProgressCard { roller, track } =>
+                format!("drawing_progress_card {} {}", roller, improvement_name(track)),
+            GameState::ResolvingProgressCard(card) => format!("resolving_progress_card {}", progress_card_name(card)),
+            GameState::TradeOffered { proposer } => format!("trade_offered {}", proposer),
+            GameState::TradeCountered { responder } => format!("trade_countered {}", responder),
+            GameState::MovingRobber => "moving_robber".to_string(),
+            GameState::StealingCards(player_id) => format!("stealing_cards {}", player_id),
+            GameState::GameOver => "game_over".to_string(),
+        }
+    }
+
+    pub(in super) fn parse_state(&self, words: &mut Words) -> Result<GameState, LayoutError> {
+        Ok(match words.word("state")? {
+            "setup_settlements" => GameState::SetupSettlements { first_player: words.player(self)? },
+            "setup_settlement_road" => GameState::SetupSettlementRoad {
+                first_player: words.player(self)?,
+                settlement: words.vertex(self)?,
+            },
+            "setup_cities" => GameState::SetupCities { first_player: words.player(self)? },
+            "setup_city_road" => GameState::SetupCityRoad {
+                first_player: words.player(self)?,
+                city: words.vertex(self)?,
+            },
+            "pre_roll" => GameState::PreRoll,
+            "roll" => GameState::Roll,
+            "turn" => GameState::Turn,
+            "special_building" => GameState::SpecialBuilding,
+            "drawing_dev_card" => GameState::DrawingDevCard,
+            "resolving_dev_card" => GameState::ResolvingDevCard(words.parse("development card", parse_dev_card)?, words.number("step")?),
+            "discarding" => GameState::Discarding { roller: words.player(self)? },
+            "choosing_gold" => GameState::ChoosingGold { roller: words.player(self)? },
+            "pillaging_city" => GameState::PillagingCity { roller: words.player(self)? },
+            "drawing_progress_card" => GameState::DrawingProgressCard {
+                roller: words.player(self)?,
+                track: words.parse("improvement", parse_improvement)?,
+            },
+            "resolving_progress_card" => GameState::ResolvingProgressCard(words.parse("progress card", parse_progress_card)?),
+            "trade_offered" => GameState::TradeOffered { proposer: words.player(self)? },
+            "trade_countered" => GameState::TradeCountered { responder: words.player(self)? },
+            "moving_robber" => GameState::MovingRobber,
+            "stealing_cards" => GameState::StealingCards(words.player(self)?),
+            "game_over" => GameState::GameOver,
+            other => return Err(malformed(words.line, format!("unknown state '{}'", other))),
+        })
+    }
+
+    pub fn to_save(&self) -> String {
+        let hexes = self.hexes.iter().map(|(pos, hex)| (*pos, hex.static_data.typ, hex.static_data.roll)).collect();
+        let players = self.players.iter().map(|(id, player)| {
+            let player = player.borrow();
+            (*id, player.static_data.color, player.static_data.next_player)
+        }).collect();
+        let mut out = write_layout(hexes, players, self.first_player);
+
+        let rules = &self.rules;
+        let setup_order = match rules.setup_order {
+            SetupOrder::Snake => "snake",
+            SetupOrder::Repeated => "repeated",
+        };
+        let dev_deck: Vec<String> = rules.dev_deck.iter().map(|&(card, num)| format!("{}:{}", dev_card_name(card), num)).collect();
+        out.push_str("\n");
+        out.push_str(&format!("rules victory_points {}\n", rules.victory_points));
+        out.push_str(&format!("rules hand_limit {}\n", rules.hand_limit));
+        out.push_str(&format!("rules dev_deck {}\n", dev_deck.join(" ")));
+        out.push_str(&format!("rules bank_size {}\n", rules.bank_size));
+        out.push_str(&format!("rules max_roads {}\n", rules.max_roads));
+        out.push_str(&format!("rules max_settlements {}\n", rules.max_settlements));
+        out.push_str(&format!("rules max_cities {}\n", rules.max_cities));
+        out.push_str(&format!("rules max_ships {}\n", rules.max_ships));
+        out.push_str(&format!("rules friendly_robber {}\n", rules.friendly_robber));
+        out.push_str(&format!("rules friendly_robber_points {}\n", rules.friendly_robber_points));
+        out.push_str(&format!("rules setup_order {}\n", setup_order));
+        out.push_str(&format!("rules setup_city {}\n", rules.setup_city));
+        out.push_str(&format!("rules special_building_phase {}\n", rules.special_building_phase));
+        out.push_str(&format!("rules seafarers {}\n", rules.seafarers));
+        out.push_str(&format!("rules island_bonus {}\n", rules.island_bonus));
+        out.push_str(&format!("rules cities_knights {}\n", rules.cities_knights));
+        out.push_str(&format!("rules barbarian_distance {}\n", rules.barbarian_distance));
+        out.push_str(&format!("rules commodity_bank_size {}\n", rules.commodity_bank_size));
+
+        out.push_str("\n");
+        out.push_str(&format!("current_player {}\n", self.cur_player));
+        out.push_str(&format!("state {}\n", self.state_string(self.state)));
+        out.push_str(&format!("winner {}\n", player_string(self.winner)));
+        out.push_str(&format!("robber {} {}\n", self.robber_pos.q(), self.robber_pos.r()));
+        match self.pirate_pos {
+            Some(pos) => out.push_str(&format!("pirate {} {}\n", pos.q(), pos.r())),
+            None => out.push_str("pirate none\n"),
+        }
+        let bank: Vec<String> = self.bank.iter().map(|num| num.to_string()).collect();
+        out.push_str(&format!("bank {}\n", bank.join(" ")));
+        out.push_str(&format!("largest_army {}\n", player_string(self.largest_army_owner)));
+        match self.longest_road {
+            Some((owner, len)) => out.push_str(&format!("longest_road {} {}\n", owner, len)),
+            None => out.push_str("longest_road none\n"),
+        }
+
+        let new_dev_cards: Vec<&str> = self.new_dev_cards.iter().map(|card| dev_card_name(*card)).collect();
+        let dev_deck: Vec<String> = self.dev_deck.iter().map(|&(card, num)| format!("{}:{}", dev_card_name(card), num)).collect();
+        out.push_str(&format!("new_dev_cards {}\n", new_dev_cards.join(" ")));
+        out.push_str(&format!("dev_deck {}\n", dev_deck.join(" ")));
+        out.push_str(&format!("dev_card_played {}\n", self.dev_card_played));
+        out.push_str(&format!("has_rolled {}\n", self.has_rolled));
+        out.push_str(&format!("special_building {}\n", player_string(self.special_building)));
+
+        if let Some(ref offer) = self.pending_trade {
+            out.push_str(&format!("trade {} {} give {} take {}\n", offer.from, offer.to, cards_string(&offer.give), cards_string(&offer.take)));
+        }
+        out.push_str(&format!("trades_offered {}\n", self.trades_offered));
+
+        let gold_owed: Vec<String> = self.gold_owed.iter().map(|&(id, num)| format!("{}:{}", id, num)).collect();
+        out.push_str(&format!("gold_owed {}\n", gold_owed.join(" ")));
+        let mut home_islands: Vec<String> = self.home_islands.iter().map(|island| self.island_string(*island)).collect();
+        home_islands.sort();
+        for island in home_islands {
+            out.push_str(&format!("home_island {}\n", island));
+        }
+
+        out.push_str(&format!("barbarians {} {}\n", self.barbarian_pos, self.barbarians_attacked));
+        for track in ALL_IMPROVEMENTS.iter() {
+            if let Some(owner) = self.metropolises[*track as usize] {
+                out.push_str(&format!("metropolis {} {}\n", improvement_name(*track), owner));
+            }
+            let deck: Vec<String> = self.progress_decks[*track as usize].iter()
+                .map(|&(card, num)| format!("{}:{}", progress_card_name(card), num))
+                .collect();
+            out.push_str(&format!("progress_deck {} {}\n", improvement_name(*track), deck.join(" ")));
+        }
+        match self.pending_roll {
+            Some(roll) => out.push_str(&format!("pending_roll {}\n", roll)),
+            None => out.push_str("pending_roll none\n"),
+        }
+        let pillaged: Vec<String> = self.pillaged.iter().map(|id| id.to_string()).collect();
+        out.push_str(&format!("pillaged {}\n", pillaged.join(" ")));
+        let progress_draws: Vec<String> = self.progress_draws.iter()
+            .map(|&(id, track)| format!("{}:{}", id, improvement_name(track)))
+            .collect();
+        out.push_str(&format!("progress_draws {}\n", progress_draws.join(" ")));
+
+        let mut pieces = Vec::new();
+        for (vertex_id, vertex) in self.vertices.iter() {
+            let desc = match vertex.structure {
+                Some((Structure::Settlement, owner)) => format!("settlement {}", owner),
+                Some((Structure::City, owner)) => format!("city {}", owner),
+                Some((Structure::Knight { level, active }, owner)) =>
+                    format!("knight {} {} {}", level, if active { "active" } else { "inactive" }, owner),
+                None => continue,
+            };
+            pieces.push(format!("vertex {} {}\n", self.vertex_string(*vertex_id), desc));
+        }
+        for (edge_id, edge) in self.edges.iter() {
+            if let Some(owner) = edge.road {
+                pieces.push(format!("edge {} road {}\n", self.edge_string(*edge_id), owner));
+            }
+            if let Some(owner) = edge.ship {
+                pieces.push(format!("edge {} ship {}\n", self.edge_string(*edge_id), owner));
+            }
+        }
+        pieces.sort();
+        out.push_str("\n");
+        for piece in pieces {
+            out.push_str(&piece);
+        }
+
+        let mut ids: Vec<PlayerID> = self.players.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let player = self.players.get(&id).unwrap().borrow();
+            let mut cards: Vec<(Resource, u8)> = player.cards.iter().map(|(res, num)| (*res, *num)).filter(|&(_, num)| num > 0).collect();
+            cards.sort_by_key(|&(res, _)| res as usize);
+            let dev_cards: Vec<&str> = player.dev_cards.iter().map(|card| dev_card_name(*card)).collect();
+            let progress_cards: Vec<&str> = player.progress_cards.iter().map(|card| progress_card_name(*card)).collect();
+
+            out.push_str("\n");
+            out.push_str(&format!("seat {} points {}\n", id, player.victory_points));
+            out.push_str(&format!("seat {} cards {}\n", id, cards_string(&cards)));
+            out.push_str(&format!("seat {} dev_cards {}\n", id, dev_cards.join(" ")));
+            out.push_str(&format!("seat {} progress_cards {}\n", id, progress_cards.join(" ")));
+            out.push_str(&format!("seat {} soldiers {}\n", id, player.soldiers));
+            out.push_str(&format!("seat {} pieces {} {} {} {} {} {} {}\n", id,
+                player.roads_left, player.settlements_left, player.cities_left, player.ships_left,
+                player.knights_left[0], player.knights_left[1], player.knights_left[2]));
+            out.push_str(&format!("seat {} improvements {} {} {}\n", id,
+                player.improvements[0], player.improvements[1], player.improvements[2]));
+            let mut islands: Vec<String> = player.islands.iter().map(|island| self.island_string(*island)).collect();
+            islands.sort();
+            for island in islands {
+                out.push_str(&format!("seat {} island {}\n", id, island));
+            }
+        }
+
+        out
+    }
+
+    pub fn from_save(text: &str) -> Result<Catan, LayoutError> {
+        // split off the board, keeping line numbers the same for error messages
+        let mut board = String::new();
+        let mut lines = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            match line.split_whitespace().next() {
+                Some("hex") | Some("player") | Some("order") => board.push_str(line),
+                Some(word) if !word.starts_with('#') => lines.push((index + 1, line)),
+                _ => { }
+            }
+            board.push('\n');
+        }
+        let mut builder = BoardBuilder::from_layout(&board)?;
+
+        // the rules decide how the bank and players are set up, so they go first
+        let mut rules = CatanRules::base_game();
+        for &(line, text) in lines.iter() {
+            let mut words = Words::new(line, text);
+            if words.word("entry")? != "rules" {
+                continue;
+            }
+            match words.word("rule")? {
+                "victory_points" => rules.victory_points = words.number("points")?,
+                "hand_limit" => rules.hand_limit = words.number("hand limit")?,
+                "dev_deck" => {
+                    let deck = words.counts("development card", parse_dev_card)?;
+                    if deck.len() != rules.dev_deck.len() {
+                        return Err(malformed(line, format!("expected all {} development cards", rules.dev_deck.len())));
+                    }
+                    for (entry, card) in rules.dev_deck.iter_mut().zip(deck) {
+                        *entry = card;
+                    }
+                }
+                "bank_size" => rules.bank_size = words.number("bank size")?,
+                "max_roads" => rules.max_roads = words.number("roads")?,
+                "max_settlements" => rules.max_settlements = words.number("settlements")?,
+                "max_cities" => rules.max_cities = words.number("cities")?,
+                "max_ships" => rules.max_ships = words.number("ships")?,
+                "friendly_robber" => rules.friendly_robber = words.flag("friendly robber")?,
+                "friendly_robber_points" => rules.friendly_robber_points = words.number("friendly robber points")?,
+                "setup_order" => rules.setup_order = words.parse("setup order", |word| match word {
+                    "snake" => Some(SetupOrder::Snake),
+                    "repeated" => Some(SetupOrder::Repeated),
+                    _ => None,
+                })?,
+                "setup_city" => rules.setup_city = words.flag("setup city")?,
+                "special_building_phase" => rules.special_building_phase = words.flag("special building phase")?,
+                "seafarers" => rules.seafarers = words.flag("seafarers")?,
+                "island_bonus" => rules.island_bonus = words.number("island bonus")?,
+                "cities_knights" => rules.cities_knights = words.flag("cities & knights")?,
+                "barbarian_distance" => rules.barbarian_distance = words.number("barbarian distance")?,
+                "commodity_bank_size" => rules.commodity_bank_size = words.number("commodity bank size")?,
+                other => return Err(malformed(line, format!("unknown rule '{}'", other))),
+            }
+            words.end()?;
+        }
+        builder.set_rules(rules);
+
+        let mut catan = Catan::new(builder);
+        for &(line, text) in lines.iter() {
+            let mut words = Words::new(line, text);
+            match words.word("entry")? {
+                "rules" => continue,
+                "current_player" => catan.cur_player = words.player(&catan)?,
+                "state" => catan.state = catan.parse_state(&mut words)?,
+                "winner" => catan.winner = words.player_or_none(&catan)?,
+                "robber" => catan.robber_pos = words.hex(&catan)?,
+                "pirate" => catan.pirate_pos = words.hex_or_none(&catan)?,
+                "bank" => {
+                    for num in catan.bank.iter_mut() {
+                        *num = words.number("bank count")?;
+                    }
+                }
+                "largest_army" => catan.largest_army_owner = words.player_or_none(&catan)?,
+                "longest_road" => {
+                    catan.longest_road = match words.player_or_none(&catan)? {
+                        Some(owner) => Some((owner, words.number("road length")?)),
+                        None => None,
+                    };
+                }
+                "new_dev_cards" => catan.new_dev_cards = words.list("development card", parse_dev_card)?,
+                "dev_deck" => {
+                    let deck = words.counts("development card", parse_dev_card)?;
+                    if deck.len() != catan.dev_deck.len() {
+                        return Err(malformed(line, format!("expected all {} development cards", catan.dev_deck.len())));
+                    }
+                    for (entry, card) in catan.dev_deck.iter_mut().zip(deck) {
+                        *entry = card;
+                    }
+                }
+                "dev_card_played" => catan.dev_card_played = words.flag("dev card played")?,
+                "has_rolled" => catan.has_rolled = words.flag("has rolled")?,
+                "special_building" => catan.special_building = words.player_or_none(&catan)?,
+                "trade" => {
+                    let from = words.player(&catan)?;
+                    let to = words.player(&catan)?;
+                    let rest: Vec<&str> = words.words.by_ref().collect();
+                    let split = rest.iter().position(|word| *word == "take");
+                    if rest.first() != Some(&"give") || split.is_none() {
+                        return Err(malformed(line, "expected 'give <cards> take <cards>'".to_string()));
+                    }
+                    let give = rest[1..split.unwrap()].join(" ");
+                    let take = rest[split.unwrap() + 1..].join(" ");
+                    catan.pending_trade = Some(TradeOffer {
+                        from,
+                        to,
+                        give: Words::new(line, &give).counts("resource", parse_card)?,
+                        take: Words::new(line, &take).counts("resource", parse_card)?,
+                    });
+                }
+                "trades_offered" => catan.trades_offered = words.number("trades offered")?,
+                "gold_owed" => catan.gold_owed = words.counts("player", |word| word.parse().ok())?,
+                "home_island" => {
+                    let vertex = words.vertex(&catan)?;
+                    match catan.islands.get(&vertex) {
+                        Some(island) => { catan.home_islands.insert(*island); }
+                        None => return Err(malformed(line, "that vertex isn't on an island".to_string())),
+                    }
+                }
+                "barbarians" => {
+                    catan.barbarian_pos = words.number("barbarian position")?;
+                    catan.barbarians_attacked = words.flag("barbarians attacked")?;
+                }
+                "metropolis" => {
+                    let track = words.parse("improvement", parse_improvement)?;
+                    catan.metropolises[track as usize] = Some(words.player(&catan)?);
+                }
+                "progress_deck" => {
+                    let track = words.parse("improvement", parse_improvement)?;
+                    catan.progress_decks[track as usize] = words.counts("progress card", parse_progress_card)?;
+                }
+                "pending_roll" => {
+                    catan.pending_roll = match words.word("roll")? {
+                        "none" => None,
+                        roll => Some(parse_number(line, "roll", Some(roll))?),
+                    };
+                }
+                "pillaged" => catan.pillaged = words.list("player", |word| word.parse().ok())?,
+                "progress_draws" => {
+                    catan.progress_draws = words.list("player:improvement pair", |word| {
+                        let mut parts = word.splitn(2, ':');
+                        match (parts.next().and_then(|id| id.parse().ok()), parts.next().and_then(parse_improvement)) {
+                            (Some(id), Some(track)) => Some((id, track)),
+                            _ => None,
+                        }
+                    })?;
+                }
+                "vertex" => {
+                    let vertex_id = words.vertex(&catan)?;
+                    let structure = match words.word("building")? {
+                        "settlement" => Structure::Settlement,
+                        "city" => Structure::City,
+                        "knight" => Structure::Knight {
+                            level: words.number("knight level")?,
+                            active: words.parse("knight state", |word| match word {
+                                "active" => Some(true),
+                                "inactive" => Some(false),
+                                _ => None,
+                            })?,
+                        },
+                        other => return Err(malformed(line, format!("unknown building '{}'", other))),
+                    };
+                    let owner = words.player(&catan)?;
+                    catan.vertices.get_mut(&vertex_id).unwrap().structure = Some((structure, owner));
+                }
+                "edge" => {
+                    let edge_id = words.edge(&catan)?;
+                    let piece = words.word("piece")?;
+                    let owner = words.player(&catan)?;
+                    let edge = catan.edges.get_mut(&edge_id).unwrap();
+                    match piece {
+                        "road" => edge.road = Some(owner),
+                        "ship" => edge.ship = Some(owner),
+                        other => return Err(malformed(line, format!("unknown piece '{}'", other))),
+                    }
+                }
+                "seat" => {
+                    let id = words.player(&catan)?;
+                    let mut player = catan.players.get(&id).unwrap().borrow_mut();
+                    match words.word("field")? {
+                        "points" => player.victory_points = words.number("points")?,
+                        "cards" => {
+                            player.cards.clear();
+                            for (res, num) in words.counts("resource", parse_card)? {
+                                player.cards.insert(res, num);
+                            }
+                        }
+                        "dev_cards" => player.dev_cards = words.list("development card", parse_dev_card)?,
+                        "progress_cards" => player.progress_cards = words.list("progress card", parse_progress_card)?,
+                        "soldiers" => player.soldiers = words.number("soldiers")?,
+                        "pieces" => {
+                            player.roads_left = words.number("roads")?;
+                            player.settlements_left = words.number("settlements")?;
+                            player.cities_left = words.number("cities")?;
+                            player.ships_left = words.number("ships")?;
+                            for num in player.knights_left.iter_mut() {
+                                *num = words.number("knights")?;
+                            }
+                        }
+                        "improvements" => {
+                            for level in player.improvements.iter_mut() {
+                                *level = words.number("improvement level")?;
+                            }
+                        }
+                        "island" => {
+                            let vertex = words.vertex(&catan)?;
+                            match catan.islands.get(&vertex) {
+                                Some(island) => { player.islands.insert(*island); }
+                                None => return Err(malformed(line, "that vertex isn't on an island".to_string())),
+                            }
+                        }
+                        other => return Err(malformed(line, format!("unknown player field '{}'", other))),
+                    }
+                }
+                other => return Err(malformed(line, format!("unknown entry '{}'", other))),
+            }
+            words.end()?;
+        }
+
+        // the rest follows from what's on the board
+        let mut buildings = Vec::new();
+        for (vertex_id, vertex) in catan.vertices.iter() {
+            if let Some((structure, owner)) = vertex.structure {
+                match structure {
+                    Structure::Settlement => { catan.players.get(&owner).unwrap().borrow_mut().settlements.insert(*vertex_id); }
+                    Structure::City => { catan.players.get(&owner).unwrap().borrow_mut().cities.insert(*vertex_id); }
+                    _ => { }
+                }
+                if vertex.building_owner().is_some() {
+                    buildings.push((*vertex_id, owner));
+                }
+            }
+        }
+        for (vertex_id, owner) in buildings {
+            catan.check_for_port(vertex_id, owner);
+        }
+        for (edge_id, edge) in catan.edges.iter() {
+            if let Some(owner) = edge.road {
+                catan.players.get(&owner).unwrap().borrow_mut().roads.insert(*edge_id);
+            }
+            if let Some(owner) = edge.ship {
+                catan.players.get(&owner).unwrap().borrow_mut().ships.insert(*edge_id);
+            }
+        }
+
+        Ok(catan)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), LayoutError> {
+        File::create(path)?.write_all(self.to_save().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Catan, LayoutError> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Catan::from_save(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+
+    use catan::*;
+    use catan::testing::*;
+
+    fn assert_round_trip(catan: &Catan) {
+        let text = catan.to_save();
+        let reloaded = Catan::from_save(&text).unwrap();
+        assert_eq!(reloaded.to_save(), text);
+        assert_eq!(sorted_moves(&reloaded), sorted_moves(catan));
+        for (player_id, player) in catan.players.iter() {
+            let player = player.borrow();
+            let other = reloaded.players[player_id].borrow();
+            assert_eq!(other.settlements, player.settlements);
+            assert_eq!(other.cities, player.cities);
+        }
+    }
+
+    #[test]
+    fn saves_restore_mid_game_positions() {
+        let (mut saw_setup, mut saw_robber, mut saw_trade, mut saw_new_dev_cards) = (false, false, false, false);
+        let all_rules = [CatanRules::base_game(), CatanRules::seafarers(), CatanRules::cities_and_knights()];
+        for rules in all_rules.iter() {
+            for seed in 0..2 {
+                play_randomly(*rules, seed, 600, |catan| {
+                    assert_round_trip(catan);
+
+                    match catan.state {
+                        GameState::SetupSettlements { .. } | GameState::SetupSettlementRoad { .. } |
+                        GameState::SetupCities { .. } | GameState::SetupCityRoad { .. } => saw_setup = true,
+                        GameState::MovingRobber | GameState::StealingCards(_) => saw_robber = true,
+                        _ => { }
+                    }
+                    saw_trade |= catan.pending_trade.is_some();
+                    saw_new_dev_cards |= !catan.new_dev_cards.is_empty();
+                });
+            }
+        }
+        assert!(saw_setup && saw_robber && saw_trade && saw_new_dev_cards);
+    }
+
+    #[test]
+    fn saves_keep_the_seat_order() {
+        let mut layout = String::new();
+        File::open(BASE_GAME).unwrap().read_to_string(&mut layout).unwrap();
+        let layout = layout.replace("order 1 2 3", "order 2 3 1");
+        let catan = Catan::new(BoardBuilder::from_layout(&layout).unwrap());
+
+        let text = catan.to_save();
+        assert!(text.contains("order 2 3 1\n"));
+
+        let reloaded = Catan::from_save(&text).unwrap();
+        assert_eq!(reloaded.first_player, 2);
+        assert_eq!(reloaded.to_save(), text);
+        assert_eq!(sorted_moves(&reloaded), sorted_moves(&catan));
+    }
+}
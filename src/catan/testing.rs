@@ -0,0 +1,55 @@
+// Helpers for the tests: the fixture board, and random games that play out the same way
+// every run.
+
+use rand::{ Rng, XorShiftRng };
+
+use ai::{ Game, MoveList };
+pub(crate) use ai::seeded_rng;
+use catan::*;
+
+pub const BASE_GAME: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/base_game.board");
+
+pub fn game_with(rules: CatanRules) -> Catan {
+    let mut builder = BoardBuilder::load(BASE_GAME).unwrap();
+    builder.set_rules(rules);
+    Catan::new(builder)
+}
+
+// The available moves in an order that doesn't depend on HashMap iteration, and whether
+// they're down to chance. Choices all get a weight of 1.
+pub fn sorted_moves(catan: &Catan) -> (bool, Vec<(CatanMove, f64)>) {
+    let (is_random, mut mvs) = match catan.available_moves() {
+        MoveList::Choice(mvs) => (false, mvs.into_iter().map(|mv| (mv, 1.0)).collect::<Vec<_>>()),
+        MoveList::Random(mvs) => (true, mvs),
+    };
+    mvs.sort_by_key(|&(ref mv, _)| format!("{:?}", mv));
+    (is_random, mvs)
+}
+
+pub fn random_move(catan: &Catan, rng: &mut XorShiftRng) -> Option<CatanMove> {
+    let (_, mvs) = sorted_moves(catan);
+    let total: f64 = mvs.iter().map(|&(_, weight)| weight).sum();
+    let n = rng.next_f64() * total;
+    let mut sum = 0.0;
+    for &(ref mv, weight) in mvs.iter() {
+        sum += weight;
+        if sum >= n {
+            return Some(mv.clone());
+        }
+    }
+    mvs.last().map(|&(ref mv, _)| mv.clone())
+}
+
+// Plays random moves from the start of a game until it ends or `max_moves` have been made,
+// calling `check` on every position along the way
+pub fn play_randomly<F>(rules: CatanRules, seed: u64, max_moves: usize, mut check: F) where F: FnMut(&mut Catan) {
+    let mut catan = game_with(rules);
+    let mut rng = seeded_rng(seed);
+    for _ in 0..max_moves {
+        check(&mut catan);
+        match random_move(&catan, &mut rng) {
+            Some(mv) => catan.make_move(&mv),
+            None => break,
+        }
+    }
+}
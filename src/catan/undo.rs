@@ -0,0 +1,255 @@
+// Taking moves back without cloning the whole board. Before a move is made, everything it
+// could change is copied into an `Undo`: the few vertices and edges it touches, the players
+// and the rest of the game state, which are all small. Putting those back restores the game
+// exactly, including dice, steals, the robber and the longest road and largest army.
+
+use std::collections::HashSet;
+
+use ai::Game;
+use catan::*;
+use catan::catan::TradeOffer;
+use catan::player::Player;
+use catan::vertex::Structure;
+
+pub struct Undo {
+    vertices: Vec<(VertexID, Option<(Structure, PlayerID)>)>,
+    edges: Vec<(EdgeID, Option<PlayerID>, Option<PlayerID>)>, // road, ship
+    players: Vec<(PlayerID, Player)>,
+
+    cur_player: PlayerID,
+    state: GameState,
+    winner: Option<PlayerID>,
+
+    robber_pos: HexCoord,
+    pirate_pos: Option<HexCoord>,
+    bank: [u8; 8],
+    largest_army_owner: Option<PlayerID>,
+    longest_road: Option<(PlayerID, u8)>,
+
+    new_dev_cards: Vec<DevCard>,
+    dev_deck: [(DevCard, u8); 5],
+    dev_card_played: bool,
+    has_rolled: bool,
+    special_building: Option<PlayerID>,
+
+    pending_trade: Option<TradeOffer>,
+    trades_offered: u8,
+
+    gold_owed: Vec<(PlayerID, u8)>,
+    home_islands: HashSet<usize>,
+
+    barbarian_pos: u8,
+    barbarians_attacked: bool,
+    metropolises: [Option<PlayerID>; 3],
+    progress_decks: [Vec<(ProgressCard, u8)>; 3],
+    pending_roll: Option<u8>,
+    pillaged: Vec<PlayerID>,
+    progress_draws: Vec<(PlayerID, Improvement)>,
+}
+
+impl Catan {
+    // The vertices and edges a move might change
+    fn touched_by(&self, mv: &CatanMove, vertices: &mut Vec<VertexID>, edges: &mut Vec<EdgeID>) {
+        match mv {
+            &CatanMove::Series(ref moves) => {
+                for mv in moves.iter() {
+                    self.touched_by(mv, vertices, edges);
+                }
+            }
+            &CatanMove::PlaceSettlement(vertex) | &CatanMove::PlaceCity(vertex) |
+            &CatanMove::BuildSettlement(vertex) | &CatanMove::BuildCity(vertex) |
+            &CatanMove::BuildKnight(vertex) | &CatanMove::ActivateKnight(vertex) |
+            &CatanMove::PromoteKnight(vertex) | &CatanMove::ChaseRobber(vertex) |
+            &CatanMove::LoseCity(vertex) => vertices.push(vertex),
+            &CatanMove::PlaceRoad(edge) | &CatanMove::BuildRoad(edge) | &CatanMove::BuildShip(edge) => edges.push(edge),
+
+            // the barbarians and the warlord can change every knight on the board
+            &CatanMove::RollDice { .. } | &CatanMove::PlayProgressCard(ProgressCard::Warlord) => {
+                for (vertex_id, vertex) in self.vertices.iter() {
+                    if let Some((Structure::Knight { .. }, _)) = vertex.structure {
+                        vertices.push(*vertex_id);
+                    }
+                }
+            }
+            _ => { }
+        }
+    }
+
+    // Makes a move, returning what's needed to take it back with `unmake_move`
+    pub fn make_move_with_undo(&mut self, mv: &CatanMove) -> Undo {
+        let mut vertex_ids = Vec::new();
+        let mut edge_ids = Vec::new();
+        self.touched_by(mv, &mut vertex_ids, &mut edge_ids);
+
+        let undo = Undo {
+            vertices: vertex_ids.iter().map(|id| (*id, self.vertices.get(id).unwrap().structure)).collect(),
+            edges: edge_ids.iter().map(|id| {
+                let edge = self.edges.get(id).unwrap();
+                (*id, edge.road, edge.ship)
+            }).collect(),
+            players: self.players.iter().map(|(id, player)| (*id, player.borrow().clone())).collect(),
+
+            cur_player: self.cur_player,
+            state: self.state,
+            winner: self.winner,
+
+            robber_pos: self.robber_pos,
+            pirate_pos: self.pirate_pos,
+            bank: self.bank,
+            largest_army_owner: self.largest_army_owner,
+            longest_road: self.longest_road,
+
+            new_dev_cards: self.new_dev_cards.clone(),
+            dev_deck: self.dev_deck,
+            dev_card_played: self.dev_card_played,
+            has_rolled: self.has_rolled,
+            special_building: self.special_building,
+
+            pending_trade: self.pending_trade.clone(),
+            trades_offered: self.trades_offered,
+
+            gold_owed: self.gold_owed.clone(),
+            home_islands: self.home_islands.clone(),
+
+            barbarian_pos: self.barbarian_pos,
+            barbarians_attacked: self.barbarians_attacked,
+            metropolises: self.metropolises,
+            progress_decks: self.progress_decks.clone(),
+            pending_roll: self.pending_roll,
+            pillaged: self.pillaged.clone(),
+            progress_draws: self.progress_draws.clone(),
+        };
+
+        self.make_move(mv);
+        undo
+    }
+
+    // Takes back the move `undo` came from. Moves have to be taken back in the reverse of
+    // the order they were made in.
+    pub fn unmake_move(&mut self, undo: Undo) {
+        // vertices are restored backwards, in case a series touched the same one twice
+        for (vertex_id, structure) in undo.vertices.into_iter().rev() {
+            self.vertices.get_mut(&vertex_id).unwrap().structure = structure;
+        }
+        for (edge_id, road, ship) in undo.edges.into_iter().rev() {
+            let edge = self.edges.get_mut(&edge_id).unwrap();
+            edge.road = road;
+            edge.ship = ship;
+        }
+        for (player_id, player) in undo.players {
+            *self.players.get(&player_id).unwrap().borrow_mut() = player;
+        }
+
+        self.cur_player = undo.cur_player;
+        self.state = undo.state;
+        self.winner = undo.winner;
+
+        self.robber_pos = undo.robber_pos;
+        self.pirate_pos = undo.pirate_pos;
+        self.bank = undo.bank;
+        self.largest_army_owner = undo.largest_army_owner;
+        self.longest_road = undo.longest_road;
+
+        self.new_dev_cards = undo.new_dev_cards;
+        self.dev_deck = undo.dev_deck;
+        self.dev_card_played = undo.dev_card_played;
+        self.has_rolled = undo.has_rolled;
+        self.special_building = undo.special_building;
+
+        self.pending_trade = undo.pending_trade;
+        self.trades_offered = undo.trades_offered;
+
+        self.gold_owed = undo.gold_owed;
+        self.home_islands = undo.home_islands;
+
+        self.barbarian_pos = undo.barbarian_pos;
+        self.barbarians_attacked = undo.barbarians_attacked;
+        self.metropolises = undo.metropolises;
+        self.progress_decks = undo.progress_decks;
+        self.pending_roll = undo.pending_roll;
+        self.pillaged = undo.pillaged;
+        self.progress_draws = undo.progress_draws;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ai::Game;
+    use catan::*;
+    use catan::testing::*;
+    use catan::vertex::Structure;
+
+    fn assert_undoes(catan: &mut Catan, mv: &CatanMove) {
+        let (hash, save) = (catan.get_hash(), catan.to_save());
+        let undo = catan.make_move_with_undo(mv);
+        catan.unmake_move(undo);
+        assert_eq!(catan.get_hash(), hash, "hash changed after undoing {:?}", mv);
+        assert_eq!(catan.to_save(), save, "state changed after undoing {:?}", mv);
+    }
+
+    #[test]
+    fn unmaking_restores_the_position() {
+        let all_rules = [CatanRules::base_game(), CatanRules::seafarers(), CatanRules::cities_and_knights()];
+        for (n, rules) in all_rules.iter().enumerate() {
+            for seed in 0..2 {
+                let mut rng = seeded_rng(100 + seed);
+                play_randomly(*rules, n as u64 * 10 + seed, 400, |catan| {
+                    for _ in 0..2 {
+                        if let Some(mv) = random_move(catan, &mut rng) {
+                            assert_undoes(catan, &mv);
+                        }
+                    }
+
+                    // a series, made of this move and one after it
+                    if let Some(first) = random_move(catan, &mut rng) {
+                        let mut after = catan.clone();
+                        after.make_move(&first);
+                        if let Some(second) = random_move(&after, &mut rng) {
+                            assert_undoes(catan, &CatanMove::Series(vec![first, second]));
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    // The warlord and the barbarians change knights all over the board
+    #[test]
+    fn unmaking_restores_every_knight() {
+        let (mut warlords, mut attacks) = (0, 0);
+        for seed in 0..4 {
+            play_randomly(CatanRules::cities_and_knights(), seed, 1500, |catan| {
+                let has_knights = catan.vertices.values().any(|vertex| match vertex.structure {
+                    Some((Structure::Knight { .. }, _)) => true,
+                    _ => false,
+                });
+                if !has_knights {
+                    return;
+                }
+
+                if catan.state == GameState::Turn && warlords < 20 {
+                    let mut game = catan.clone();
+                    let cur_player = game.cur_player;
+                    game.players.get(&cur_player).unwrap().borrow_mut().progress_cards.push(ProgressCard::Warlord);
+                    assert_undoes(&mut game, &CatanMove::PlayProgressCard(ProgressCard::Warlord));
+                    warlords += 1;
+                }
+
+                let (_, mvs) = sorted_moves(catan);
+                let ship = mvs.iter().find(|&&(ref mv, _)| match *mv {
+                    CatanMove::RollDice { event: EventDie::Ship, .. } => true,
+                    _ => false,
+                });
+                if let Some(&(ref roll, _)) = ship {
+                    if attacks < 20 {
+                        let mut game = catan.clone();
+                        game.barbarian_pos = game.rules.barbarian_distance - 1;
+                        assert_undoes(&mut game, roll);
+                        attacks += 1;
+                    }
+                }
+            });
+        }
+        assert!(warlords > 0 && attacks > 0);
+    }
+}
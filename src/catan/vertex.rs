@@ -1,5 +1,6 @@
 use std::sync::Arc;
 use std::hash::*;
+use std::f64::consts::PI;
 
 use cairo::Context;
 
@@ -7,7 +8,8 @@ use catan::*;
 
 #[derive(Clone, Copy, Hash)]
 pub enum Structure {
-    Settlement, City
+    Settlement, City,
+    Knight { level: u8, active: bool }, // Cities & Knights
 }
 
 pub struct VertexStatic {
@@ -33,6 +35,14 @@ impl Vertex {
         }
     }
 
+    // The owner of the settlement or city here; knights don't count
+    pub fn building_owner(&self) -> Option<PlayerID> {
+        match self.structure {
+            Some((Structure::Settlement, player)) | Some((Structure::City, player)) => Some(player),
+            _ => None,
+        }
+    }
+
     pub fn draw(&self, ctx: &Context, catan: &Catan, id: VertexID) {
         if let Some((structure, player)) = self.structure {
             let pos = self.static_data.hex_position;
@@ -68,6 +78,19 @@ impl Vertex {
                     ctx.line_to(2.0*size, size);
                     ctx.close_path();
                 }
+                Structure::Knight { level, active } => {
+                    // inactive knights are drawn hollow, with a ring for each level
+                    for ring in 0..level {
+                        let radius = size * (1.5 - ring as f64 * 0.5);
+                        ctx.move_to(radius, 0.0);
+                        ctx.arc(0.0, 0.0, radius, 0.0, 2.0*PI);
+                    }
+                    if !active {
+                        ctx.set_line_width(2.0);
+                        ctx.stroke_preserve();
+                        ctx.set_source_rgb(1.0, 1.0, 1.0);
+                    }
+                }
             }
 
             ctx.fill_preserve();
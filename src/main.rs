@@ -9,6 +9,7 @@ extern crate rand;
 extern crate num_cpus;
 extern crate lazy_static;
 
+use std::env;
 use std::rc::Rc;
 use std::cell::{ Cell, RefCell };
 use std::time::{ Instant, Duration };
@@ -27,6 +28,7 @@ use ai::*;
 
 const AI_TURN_TIME: u64 = 3; // seconds
 const HUMAN_PLAYER: bool = false;
+const RECORD_PATH: &str = "last_game.record"; // written when the window is closed
 
 const fn color(hex: u32) -> [f64; 3] {
     [
@@ -38,7 +40,7 @@ const fn color(hex: u32) -> [f64; 3] {
 const WATER_COLOR: [f64; 3] = color(0x0026E5);
 
 
-fn main() {
+fn default_board() -> BoardBuilder {
     let mut builder = BoardBuilder::new();
 
     builder.add_hex(HexCoord::new(-3,  0), HexType::Water, 0);
@@ -91,14 +93,32 @@ fn main() {
 
     builder.set_player_order(vec![p1, p2, p3]);
 
-    let ai_player = p1;
+    builder
+}
+
+fn main() {
+    // a board file can be passed on the command line
+    let builder = match env::args().nth(1) {
+        Some(path) => match BoardBuilder::load(&path) {
+            Ok(builder) => builder,
+            Err(err) => {
+                println!("Couldn't load {}: {}", path, err);
+                return;
+            }
+        },
+        None => default_board(),
+    };
 
-    let (_catan, _ai) = {
+    let ai_player = builder.first_player;
+
+    let (_catan, _ai, record) = {
         let mut catan = Catan::new(builder);
-        let mut ai = Ai::new(catan.clone());
+        let mut ai = Ai::new(catan.clone(), SearchConfig::default());
+        let record = GameRecord::new(catan.clone());
         (
             Rc::new(RefCell::new(catan)),
-            Rc::new(RefCell::new(ai))
+            Rc::new(RefCell::new(ai)),
+            Rc::new(RefCell::new(record))
         )
     };
     let pending_move = Rc::new(Cell::new(false));
@@ -164,10 +184,16 @@ fn main() {
     window.set_title("Catan AI");
     window.set_position(WindowPosition::Center);
     window.set_default_size(1200, 720);
-    window.connect_delete_event(|_, _| {
-    	gtk::main_quit();
-    	Inhibit(false)
-    });
+    {
+        let record = record.clone();
+        window.connect_delete_event(move |_, _| {
+            if let Err(err) = record.borrow().save(RECORD_PATH) {
+                println!("Couldn't save the game to {}: {}", RECORD_PATH, err);
+            }
+            gtk::main_quit();
+            Inhibit(false)
+        });
+    }
     window.add(&container);
     window.show_all();
 
@@ -182,7 +208,7 @@ fn main() {
             let mut catan = _catan.borrow_mut();
             while let Some(res) = ai.recv() {
                 match res {
-                    Response::Info { best_move, is_random, confidence, total_sims, time_elapsed, possible_moves } => {
+                    Response::Info { best_move, is_random, confidence, total_sims, time_elapsed, possible_moves, .. } => {
 						let player = catan.get_cur_player().clone();
 
 						let move_str = if !HUMAN_PLAYER || player == ai_player {
@@ -216,6 +242,7 @@ fn main() {
 
                         if (is_random || possible_moves.len() == 1) && !pending_move.get() {
                             if let Some(mv) = rand.choose(&possible_moves) {
+                                record.borrow_mut().push(mv.clone());
                                 catan.make_move(mv);
                                 da.queue_draw();
 
@@ -224,6 +251,7 @@ fn main() {
                             }
                         } else if (!HUMAN_PLAYER || player == ai_player) && !pending_move.get() && last_move.elapsed() > Duration::from_secs(AI_TURN_TIME) {
 							if let Some(mv) = best_move {
+								record.borrow_mut().push(mv.clone());
 								catan.make_move(&mv);
 								da.queue_draw();
 